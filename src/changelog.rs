@@ -0,0 +1,209 @@
+use serde::Serialize;
+
+/// Separates commits in the `git log` output parsed by `parse_log`
+const RECORD_SEPARATOR: char = '\x1e';
+/// Separates fields of a commit in the `git log` output parsed by `parse_log`
+const FIELD_SEPARATOR: char = '\x1f';
+
+/// `git log` format matching `parse_log`: hash, author, date and subject, with the
+/// touched paths following on their own lines (`--name-only`)
+pub const LOG_FORMAT: &str = "--format=%x1e%H%x1f%an%x1f%aI%x1f%s";
+
+/// Most commits a changelog lists, whatever limit is asked for
+pub const MAX_COMMITS: usize = 5000;
+
+/// Conventional commit types in the order they are listed, most relevant first
+const TYPE_ORDER: &[&str] = &[
+    "feat", "fix", "perf", "docs", "refactor", "test", "build", "ci", "style", "chore", "revert",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitInfo {
+    pub commit: String,
+    pub subject: String,
+    pub author: String,
+    pub date: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    pub breaking: bool,
+    pub paths: Vec<String>,
+    #[serde(skip)]
+    kind: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangeGroup {
+    /// Conventional commit type (`feat`, `fix`, ...), or `other`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub commits: Vec<CommitInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Changelog {
+    pub repo: String,
+    /// Refs the requested versions resolved to
+    pub from: String,
+    pub to: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub total: usize,
+    /// Whether older commits were left out because of the limit
+    pub truncated: bool,
+    pub groups: Vec<ChangeGroup>,
+}
+
+/// Parse `git log` output produced with `LOG_FORMAT` and `--name-only`
+pub fn parse_log(output: &str) -> Vec<CommitInfo> {
+    output
+        .split(RECORD_SEPARATOR)
+        .filter_map(|record| {
+            let mut lines = record.lines();
+            let mut fields = lines.next()?.splitn(4, FIELD_SEPARATOR);
+            let commit = fields.next()?.to_string();
+            let author = fields.next()?.to_string();
+            let date = fields.next()?.to_string();
+            let subject = fields.next()?.to_string();
+            let (kind, scope, breaking) = conventional_type(&subject);
+            Some(CommitInfo {
+                commit,
+                subject,
+                author,
+                date,
+                scope,
+                breaking,
+                paths: lines.filter(|l| !l.is_empty()).map(|l| l.to_string()).collect(),
+                kind,
+            })
+        })
+        .collect()
+}
+
+/// Group commits by conventional commit type, keeping their order within each group
+pub fn group_by_type(commits: Vec<CommitInfo>) -> Vec<ChangeGroup> {
+    let mut groups: Vec<ChangeGroup> = Vec::new();
+    for commit in commits {
+        match groups.iter_mut().find(|g| g.kind == commit.kind) {
+            Some(group) => group.commits.push(commit),
+            None => groups.push(ChangeGroup {
+                kind: commit.kind.clone(),
+                commits: vec![commit],
+            }),
+        }
+    }
+    groups.sort_by_key(|g| {
+        TYPE_ORDER
+            .iter()
+            .position(|t| *t == g.kind)
+            .unwrap_or(TYPE_ORDER.len())
+    });
+    groups
+}
+
+/// Type, scope and breaking flag of a `type(scope)!: description` subject.
+/// Subjects that don't follow the convention are of type `other`.
+fn conventional_type(subject: &str) -> (String, Option<String>, bool) {
+    let other = ("other".to_string(), None, false);
+    let Some((prefix, _)) = subject.split_once(':') else {
+        return other;
+    };
+    let (prefix, breaking) = match prefix.strip_suffix('!') {
+        Some(prefix) => (prefix, true),
+        None => (prefix, false),
+    };
+    let (kind, scope) = match prefix.split_once('(') {
+        Some((kind, scope)) => match scope.strip_suffix(')') {
+            Some(scope) => (kind, Some(scope.to_string())),
+            None => return other,
+        },
+        None => (prefix, None),
+    };
+    if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphabetic()) {
+        return other;
+    }
+    let breaking = breaking || subject.contains("BREAKING CHANGE");
+    (kind.to_lowercase(), scope, breaking)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `git log` output for commits given as hash, subject and touched paths
+    fn log(commits: &[(&str, &str, &[&str])]) -> String {
+        commits
+            .iter()
+            .map(|(hash, subject, paths)| {
+                format!(
+                    "{}{}{}Ada{}2024-05-01T10:00:00+02:00{}{}\n\n{}\n",
+                    RECORD_SEPARATOR,
+                    hash,
+                    FIELD_SEPARATOR,
+                    FIELD_SEPARATOR,
+                    FIELD_SEPARATOR,
+                    subject,
+                    paths.join("\n")
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_commits_with_their_paths() {
+        let commits = parse_log(&log(&[
+            ("a1", "feat(router): add KV routing", &["docs/router.md", "src/lib.rs"]),
+            ("b2", "Subject: with a colon", &[]),
+        ]));
+        assert_eq!(commits.len(), 2);
+        let first = &commits[0];
+        assert_eq!((first.commit.as_str(), first.author.as_str()), ("a1", "Ada"));
+        assert_eq!(first.date, "2024-05-01T10:00:00+02:00");
+        assert_eq!(first.subject, "feat(router): add KV routing");
+        assert_eq!(first.paths, ["docs/router.md", "src/lib.rs"]);
+        assert_eq!(commits[1].subject, "Subject: with a colon");
+        assert!(commits[1].paths.is_empty());
+    }
+
+    #[test]
+    fn reads_conventional_commit_types() {
+        assert_eq!(conventional_type("feat: x"), ("feat".to_string(), None, false));
+        assert_eq!(
+            conventional_type("Fix(kv-cache)!: x"),
+            ("fix".to_string(), Some("kv-cache".to_string()), true)
+        );
+        assert_eq!(
+            conventional_type("refactor: x\n\nBREAKING CHANGE: y"),
+            ("refactor".to_string(), None, true)
+        );
+        for subject in ["Update README", "fix(scope: x", "fix 2: x", ": x", "v2: x"] {
+            assert_eq!(conventional_type(subject).0, "other", "{}", subject);
+        }
+    }
+
+    #[test]
+    fn groups_by_type_in_listing_order() {
+        let commits = parse_log(&log(&[
+            ("1", "chore: bump deps", &[]),
+            ("2", "fix: first fix", &[]),
+            ("3", "Merge branch main", &[]),
+            ("4", "feat: feature", &[]),
+            ("5", "fix(api): second fix", &[]),
+            ("6", "wip: unknown type", &[]),
+        ]));
+        let groups = group_by_type(commits);
+        let summary: Vec<(&str, Vec<&str>)> = groups
+            .iter()
+            .map(|g| (g.kind.as_str(), g.commits.iter().map(|c| c.commit.as_str()).collect()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("feat", vec!["4"]),
+                ("fix", vec!["2", "5"]),
+                ("chore", vec!["1"]),
+                ("other", vec!["3"]),
+                ("wip", vec!["6"]),
+            ]
+        );
+    }
+}
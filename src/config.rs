@@ -0,0 +1,476 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::forge::{ForgeConfig, ForgeKind};
+use crate::rules::{IndexRules, DEFAULT_EXCLUDE, DEFAULT_INCLUDE};
+
+/// Registry of repositories served by the MCP server.
+///
+/// Loaded from `DYNAMO_MCP_CONFIG` or `~/.config/dynamo-mcp/config.toml`; when no
+/// file exists, the built-in registry (dynamo and dynamo-dotfiles) is used.
+///
+/// ```toml
+/// [[repo]]
+/// name = "dynamo"             # name used by tools and in document ids
+/// owner = "ai-dynamo"
+/// repo = "dynamo"             # repository, defaults to `name`
+/// forge = "github"            # optional, github (default), gitlab, gitea or git
+/// version = "v0.4.0"          # optional, any version switch_version accepts; defaults to the latest release
+/// api_url = "https://github.example.com/api/v3"      # optional, for self-hosted forges
+/// clone_url = "https://git.example.com/{owner}/{repo}.git"  # optional, for mirrors
+/// include = ["docs/**/*.md"]  # optional, globs relative to the repository root
+/// exclude = ["docs/drafts/**"]
+///
+/// [repo.categories]           # optional, directory prefix -> category
+/// "docs/guides" = "guide"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(rename = "repo", default)]
+    pub repos: Vec<RepoConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoConfig {
+    pub name: String,
+    pub owner: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub categories: BTreeMap<String, String>,
+    /// Where the repository is hosted (default: GitHub)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forge: Option<ForgeKind>,
+    /// API base URL of the forge, for GitHub Enterprise and self-hosted instances
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_url: Option<String>,
+    /// Clone URL template (`{owner}`, `{repo}`) or base URL, for mirrors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clone_url: Option<String>,
+}
+
+impl RepoConfig {
+    /// Repository name on the forge
+    pub fn repo_name(&self) -> &str {
+        self.repo.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn forge_config(&self) -> ForgeConfig {
+        ForgeConfig {
+            kind: self.forge.unwrap_or_default(),
+            api_url: self.api_url.clone(),
+            clone_url: self.clone_url.clone(),
+        }
+    }
+
+    /// Prefix for per-repository environment overrides, e.g. `DYNAMO_DOTFILES`
+    fn env_prefix(&self) -> String {
+        self.name.to_uppercase().replace(['-', '.'], "_")
+    }
+
+    /// Version to set up: `{PREFIX}_VERSION` if set, otherwise the configured version
+    pub fn version(&self) -> Option<String> {
+        env::var(format!("{}_VERSION", self.env_prefix()))
+            .ok()
+            .or_else(|| self.version.clone())
+    }
+
+    /// Index rules, with `{PREFIX}_INCLUDE` / `{PREFIX}_EXCLUDE` (comma-separated
+    /// globs) taking precedence over the configured globs
+    pub fn index_rules(&self) -> Result<IndexRules> {
+        let prefix = self.env_prefix();
+        let globs = |var: String, configured: &[String], default: &[&str]| -> Vec<String> {
+            match env::var(&var) {
+                Ok(value) => value
+                    .split(',')
+                    .map(|g| g.trim().to_string())
+                    .filter(|g| !g.is_empty())
+                    .collect(),
+                Err(_) if !configured.is_empty() => configured.to_vec(),
+                Err(_) => default.iter().map(|g| g.to_string()).collect(),
+            }
+        };
+        let include = globs(format!("{}_INCLUDE", prefix), &self.include, DEFAULT_INCLUDE);
+        let exclude = globs(format!("{}_EXCLUDE", prefix), &self.exclude, DEFAULT_EXCLUDE);
+        IndexRules::new(&include, &exclude, &self.categories)
+            .with_context(|| format!("Invalid index rules for repository {}", self.name))
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let globs = |globs: &[&str]| globs.iter().map(|g| g.to_string()).collect();
+        Self {
+            repos: vec![
+                RepoConfig {
+                    name: "dynamo".to_string(),
+                    owner: "ai-dynamo".to_string(),
+                    repo: None,
+                    version: None,
+                    include: globs(&[
+                        "docs/**/*.md",
+                        "components/**/README.md",
+                        "lib/**/README.md",
+                        "examples/**/README.md",
+                        "deploy/**/README.md",
+                    ]),
+                    exclude: globs(DEFAULT_EXCLUDE),
+                    categories: BTreeMap::new(),
+                    forge: None,
+                    api_url: None,
+                    clone_url: None,
+                },
+                RepoConfig {
+                    name: "dynamo-dotfiles".to_string(),
+                    owner: "ryanolson".to_string(),
+                    repo: None,
+                    version: None,
+                    include: globs(DEFAULT_INCLUDE),
+                    exclude: globs(DEFAULT_EXCLUDE),
+                    categories: BTreeMap::new(),
+                    forge: None,
+                    api_url: None,
+                    clone_url: None,
+                },
+            ],
+        }
+    }
+}
+
+impl Config {
+    /// Path of the config file: `DYNAMO_MCP_CONFIG`, or `~/.config/dynamo-mcp/config.toml`
+    pub fn path() -> Result<PathBuf> {
+        if let Ok(path) = env::var("DYNAMO_MCP_CONFIG") {
+            return Ok(PathBuf::from(path));
+        }
+        Ok(dirs::home_dir()
+            .ok_or_else(|| anyhow::anyhow!("No home directory"))?
+            .join(".config")
+            .join("dynamo-mcp")
+            .join("config.toml"))
+    }
+
+    /// Load the registry from the config file, or the built-in one if there is none
+    pub fn load() -> Result<Self> {
+        Self::load_from(&Self::path()?)
+    }
+
+    fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        let config: Self = toml::from_str(&text).with_context(|| format!("Invalid config file {:?}", path))?;
+        config.validate().with_context(|| format!("Invalid config file {:?}", path))?;
+        Ok(config)
+    }
+
+    /// Write the registry back to the config file, replacing it atomically
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&Self::path()?)
+    }
+
+    fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("toml.tmp");
+        fs::write(&tmp, toml::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {:?}", tmp))?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        let mut seen = HashSet::new();
+        for repo in &self.repos {
+            validate_repo(repo)?;
+            if !seen.insert(repo.name.as_str()) {
+                anyhow::bail!("Repository {} is configured more than once", repo.name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that a repository can be added to the registry
+    pub fn check_new(&self, repo: &RepoConfig) -> Result<()> {
+        validate_repo(repo)?;
+        if self.get(&repo.name).is_some() {
+            anyhow::bail!("Repository {} is already configured", repo.name);
+        }
+        Ok(())
+    }
+
+    /// Register a new repository
+    pub fn add(&mut self, repo: RepoConfig) -> Result<()> {
+        self.check_new(&repo)?;
+        self.repos.push(repo);
+        Ok(())
+    }
+
+    /// Unregister a repository, returning its configuration
+    pub fn remove(&mut self, name: &str) -> Result<RepoConfig> {
+        self.require(name)?;
+        let position = self.repos.iter().position(|r| r.name == name).unwrap();
+        Ok(self.repos.remove(position))
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.repos.iter().map(|r| r.name.as_str()).collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&RepoConfig> {
+        self.repos.iter().find(|r| r.name == name)
+    }
+
+    /// Look up a repository by name, with an error listing the configured ones
+    pub fn require(&self, name: &str) -> Result<&RepoConfig> {
+        self.get(name).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown repository '{}'. Configured repositories: {}",
+                name,
+                self.names().join(", ")
+            )
+        })
+    }
+}
+
+fn validate_repo(repo: &RepoConfig) -> Result<()> {
+    // Both end up in cache paths, so they must be single path components
+    if !is_path_component(&repo.name) {
+        anyhow::bail!("Invalid repository name {:?}, expected letters, digits, '.', '_' or '-'", repo.name);
+    }
+    if !is_path_component(repo.repo_name()) {
+        anyhow::bail!(
+            "Repository {} has an invalid repo {:?}, expected letters, digits, '.', '_' or '-'",
+            repo.name,
+            repo.repo_name()
+        );
+    }
+    if repo.owner.is_empty() {
+        anyhow::bail!("Repository {} has no owner", repo.name);
+    }
+    if let Some(api_url) = &repo.api_url {
+        if !api_url.starts_with("http://") && !api_url.starts_with("https://") {
+            anyhow::bail!("Repository {} has an invalid api_url {:?}", repo.name, api_url);
+        }
+    }
+    if let Some(clone_url) = &repo.clone_url {
+        if !is_allowed_clone_url(clone_url) {
+            anyhow::bail!(
+                "Repository {} has an invalid clone_url {:?}, expected an https, http, ssh, git or file URL",
+                repo.name,
+                clone_url
+            );
+        }
+    }
+    if repo.forge == Some(ForgeKind::Git) && repo.clone_url.is_none() {
+        anyhow::bail!("Repository {} is hosted on a plain git remote and needs a clone_url", repo.name);
+    }
+    repo.index_rules()?;
+    Ok(())
+}
+
+fn is_path_component(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+/// Whether a clone URL uses https, http, ssh (including scp-like `user@host:path`), the
+/// git protocol or `file://`, for mirrors on a local or network filesystem. Bare local
+/// paths and remote helpers such as `ext::` are refused.
+fn is_allowed_clone_url(url: &str) -> bool {
+    let url = url.trim();
+    if url.starts_with('-') || url.contains("::") {
+        return false;
+    }
+    if let Some((scheme, rest)) = url.split_once("://") {
+        return matches!(scheme, "https" | "http" | "ssh" | "git" | "file") && !rest.is_empty() && !rest.starts_with('-');
+    }
+    // scp-like syntax: a host before the first colon, which precedes any slash
+    match url.split_once(':') {
+        Some((host, path)) => !host.is_empty() && !host.contains('/') && !path.is_empty(),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn repo(name: &str) -> RepoConfig {
+        RepoConfig {
+            name: name.to_string(),
+            owner: "owner".to_string(),
+            repo: None,
+            version: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            categories: BTreeMap::new(),
+            forge: None,
+            api_url: None,
+            clone_url: None,
+        }
+    }
+
+    fn parse(text: &str) -> Result<Config> {
+        let config: Config = toml::from_str(text)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    #[test]
+    fn builtin_registry_is_valid() {
+        let config = Config::default();
+        config.validate().unwrap();
+        assert_eq!(config.names(), ["dynamo", "dynamo-dotfiles"]);
+    }
+
+    #[test]
+    fn parses_and_validates_config_files() {
+        let config = parse(
+            r#"
+            [[repo]]
+            name = "docs"
+            owner = "acme"
+            repo = "documentation"
+            forge = "gitlab"
+            version = "v1.0"
+            include = ["docs/**/*.md"]
+
+            [repo.categories]
+            "docs/guides" = "guide"
+            "#,
+        )
+        .unwrap();
+        let docs = config.require("docs").unwrap();
+        assert_eq!(docs.repo_name(), "documentation");
+        assert_eq!(docs.forge_config().kind, ForgeKind::GitLab);
+        assert_eq!(docs.categories["docs/guides"], "guide");
+
+        let duplicate = "[[repo]]\nname = \"a\"\nowner = \"o\"\n[[repo]]\nname = \"a\"\nowner = \"p\"\n";
+        assert!(parse(duplicate).unwrap_err().to_string().contains("more than once"));
+        assert!(parse("[[repo]]\nname = \"a\"\nowner = \"\"\n").is_err());
+        assert!(parse("[[repo]]\nname = \"../a\"\nowner = \"o\"\n").is_err());
+        assert!(parse("[[repo]]\nname = \"a\"\nowner = \"o\"\nrepo = \"a/b\"\n").is_err());
+        assert!(parse("[[repo]]\nname = \"a\"\nowner = \"o\"\napi_url = \"ftp://host\"\n").is_err());
+        assert!(parse("[[repo]]\nname = \"a\"\nowner = \"o\"\nforge = \"git\"\n").is_err());
+        assert!(parse("[[repo]]\nname = \"a\"\nowner = \"o\"\ninclude = [\"[\"]\n").is_err());
+    }
+
+    #[test]
+    fn accepts_remote_and_file_clone_urls() {
+        for url in [
+            "https://git.example.com/{owner}/{repo}.git",
+            "http://mirror.internal/git",
+            "ssh://git@git.example.com/o/r.git",
+            "git://git.example.com/o/r.git",
+            "git@git.example.com:o/r.git",
+            "file:///srv/mirrors/{repo}.git",
+        ] {
+            assert!(is_allowed_clone_url(url), "{}", url);
+        }
+        for url in [
+            "/srv/mirrors/r.git",
+            "../r.git",
+            "ext::sh -c touch% /tmp/pwned",
+            "fd::17",
+            "-uhttps://example.com",
+            "https://",
+            "file://-u",
+            "ftp://example.com/r.git",
+            "",
+        ] {
+            assert!(!is_allowed_clone_url(url), "{}", url);
+        }
+
+        let config = "[[repo]]\nname = \"a\"\nowner = \"o\"\nforge = \"git\"\nclone_url = \"file:///srv/a.git\"\n";
+        parse(config).unwrap();
+        let config = "[[repo]]\nname = \"a\"\nowner = \"o\"\nclone_url = \"/srv/a.git\"\n";
+        assert!(parse(config).unwrap_err().to_string().contains("invalid clone_url"));
+    }
+
+    #[test]
+    fn repository_names_are_single_path_components() {
+        for name in ["dynamo", "dynamo-dotfiles", "v1.0", "a_b"] {
+            assert!(is_path_component(name), "{}", name);
+        }
+        for name in ["", ".", "..", "a/b", "a\\b", "a b", "caf\u{e9}"] {
+            assert!(!is_path_component(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn adds_and_removes_repositories() {
+        let mut config = Config::default();
+        config.add(repo("extra")).unwrap();
+        assert_eq!(config.names(), ["dynamo", "dynamo-dotfiles", "extra"]);
+
+        let err = config.add(repo("extra")).unwrap_err();
+        assert!(err.to_string().contains("already configured"));
+        assert!(config.add(repo("bad/name")).is_err());
+
+        assert_eq!(config.remove("dynamo").unwrap().owner, "ai-dynamo");
+        assert_eq!(config.names(), ["dynamo-dotfiles", "extra"]);
+        let err = config.remove("dynamo").unwrap_err();
+        assert!(err.to_string().contains("Configured repositories: dynamo-dotfiles, extra"));
+    }
+
+    #[test]
+    fn derives_environment_prefixes_from_names() {
+        assert_eq!(repo("dynamo").env_prefix(), "DYNAMO");
+        assert_eq!(repo("dynamo-dotfiles").env_prefix(), "DYNAMO_DOTFILES");
+        assert_eq!(repo("docs.v2").env_prefix(), "DOCS_V2");
+
+        // Each repository only reads its own variable, though one prefix extends the other
+        env::set_var("DYNAMO_VERSION", "v1.0.0");
+        env::set_var("DYNAMO_DOTFILES_VERSION", "main");
+        let mut dynamo = repo("dynamo");
+        dynamo.version = Some("v0.1.0".to_string());
+        assert_eq!(dynamo.version().as_deref(), Some("v1.0.0"));
+        assert_eq!(repo("dynamo-dotfiles").version().as_deref(), Some("main"));
+        env::remove_var("DYNAMO_VERSION");
+        env::remove_var("DYNAMO_DOTFILES_VERSION");
+        assert_eq!(dynamo.version().as_deref(), Some("v0.1.0"));
+        assert_eq!(repo("dynamo-dotfiles").version(), None);
+    }
+
+    #[test]
+    fn saves_and_reloads_the_registry() {
+        let dir = TempDir::new("config-save");
+        let path = dir.path().join("dynamo-mcp").join("config.toml");
+        assert_eq!(Config::load_from(&path).unwrap().names(), ["dynamo", "dynamo-dotfiles"]);
+
+        let mut config = Config::default();
+        let mut extra = repo("extra");
+        extra.version = Some("v2".to_string());
+        extra.exclude = vec!["drafts/**".to_string()];
+        config.add(extra).unwrap();
+        config.remove("dynamo-dotfiles").unwrap();
+        config.save_to(&path).unwrap();
+        assert!(!path.with_extension("toml.tmp").exists());
+
+        let reloaded = Config::load_from(&path).unwrap();
+        assert_eq!(reloaded.names(), ["dynamo", "extra"]);
+        let extra = reloaded.require("extra").unwrap();
+        assert_eq!(extra.version.as_deref(), Some("v2"));
+        assert_eq!(extra.exclude, ["drafts/**"]);
+        assert_eq!(reloaded.require("dynamo").unwrap().include.len(), 5);
+
+        fs::write(&path, "[[repo]]\nname = \"a\"\n").unwrap();
+        assert!(Config::load_from(&path).is_err());
+    }
+}
@@ -0,0 +1,398 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Response;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::http_cache::{self, next_page, HttpCache};
+
+/// Releases fetched by `Forge::list_releases` unless configured otherwise
+pub const DEFAULT_MAX_RELEASES: usize = 500;
+
+/// A published release of a repository
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub name: Option<String>,
+    /// Unset for drafts
+    pub published_at: Option<String>,
+    pub prerelease: bool,
+    pub draft: bool,
+}
+
+/// Where a repository is hosted, which decides how its releases are discovered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForgeKind {
+    #[default]
+    GitHub,
+    GitLab,
+    Gitea,
+    /// Any git remote; releases are its annotated tags
+    Git,
+}
+
+impl std::str::FromStr for ForgeKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "github" => Ok(Self::GitHub),
+            "gitlab" => Ok(Self::GitLab),
+            "gitea" => Ok(Self::Gitea),
+            "git" => Ok(Self::Git),
+            _ => anyhow::bail!("Unknown forge {:?}, expected github, gitlab, gitea or git", s),
+        }
+    }
+}
+
+/// How to reach a repository's forge: its kind, and API and clone URLs for
+/// self-hosted instances and mirrors. Unset URLs use the forge's defaults.
+#[derive(Debug, Clone, Default)]
+pub struct ForgeConfig {
+    pub kind: ForgeKind,
+    /// API base URL, e.g. `https://github.example.com/api/v3`
+    pub api_url: Option<String>,
+    /// Clone URL, either a template with `{owner}` and `{repo}` placeholders or a base
+    /// URL that `/{owner}/{repo}.git` is appended to
+    pub clone_url: Option<String>,
+}
+
+/// Release discovery for a repository hosted on some forge
+#[async_trait]
+pub trait Forge: Send + Sync {
+    /// Tag of the latest stable release, or None if there are no releases
+    async fn latest_release(&self) -> Result<Option<String>>;
+
+    /// Published (non-draft) releases, newest first
+    async fn list_releases(&self) -> Result<Vec<Release>>;
+
+    async fn default_branch(&self) -> Result<String>;
+
+    fn clone_url(&self) -> String;
+
+    /// Whether releases are read from the local clone, so they are available offline
+    fn is_local(&self) -> bool {
+        false
+    }
+}
+
+/// Expand a clone URL template, or append `/{owner}/{repo}.git` to a base URL
+pub fn expand_clone_url(template: &str, owner: &str, repo: &str) -> String {
+    if template.contains("{owner}") || template.contains("{repo}") {
+        return template.replace("{owner}", owner).replace("{repo}", repo);
+    }
+    format!("{}/{}/{}.git", template.trim_end_matches('/'), owner, repo)
+}
+
+/// Token from an environment variable, if set and not blank
+pub fn token(var: &str) -> Option<String> {
+    std::env::var(var)
+        .ok()
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+/// Whether `url` has the same scheme, host and port as `api_url`. Tokens are only sent
+/// to the configured API, not to other hosts named in `Link` headers.
+pub fn same_origin(url: &str, api_url: &str) -> bool {
+    match (reqwest::Url::parse(url), reqwest::Url::parse(api_url)) {
+        (Ok(url), Ok(api_url)) => url.origin() == api_url.origin(),
+        _ => false,
+    }
+}
+
+/// Unsuccessful response from a forge API
+#[derive(Debug, Error)]
+#[error("{url} returned {status}")]
+pub struct StatusError {
+    pub status: StatusCode,
+    pub url: String,
+}
+
+/// JSON API client shared by the GitLab and Gitea backends
+pub struct RestClient {
+    client: reqwest::Client,
+    /// Authentication header name and value, and the API URL it may be sent to
+    auth: Option<(&'static str, String, String)>,
+    cache: Option<HttpCache>,
+    max_items: usize,
+}
+
+impl RestClient {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            auth: None,
+            cache: None,
+            max_items: DEFAULT_MAX_RELEASES,
+        }
+    }
+
+    /// Send this header with every request to `api_url`, e.g. `PRIVATE-TOKEN` for GitLab
+    pub fn with_auth(mut self, header: &'static str, value: String, api_url: &str) -> Self {
+        self.auth = Some((header, value, api_url.to_string()));
+        self
+    }
+
+    pub fn with_cache(mut self, cache: HttpCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Stop paging once this many items were fetched
+    pub fn with_max_items(mut self, max_items: usize) -> Self {
+        self.max_items = max_items;
+        self
+    }
+
+    /// GET a JSON document, or None if it doesn't exist
+    pub async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<Option<T>> {
+        match self.get_body(url).await {
+            Ok((body, _)) => Ok(Some(serde_json::from_str(&body)?)),
+            Err(e) if e.downcast_ref::<StatusError>().is_some_and(|e| e.status == StatusCode::NOT_FOUND) => {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// GET a JSON list, following the `Link` header through all pages until `max_items`
+    /// were fetched. Only items passing `keep` are returned and count toward the limit.
+    pub async fn get_pages<T: DeserializeOwned>(&self, url: &str, keep: impl Fn(&T) -> bool) -> Result<Vec<T>> {
+        let mut url = Some(url.to_string());
+        let mut items: Vec<T> = Vec::new();
+        while let Some(page_url) = url.take() {
+            if items.len() >= self.max_items {
+                break;
+            }
+            let (body, link) = self.get_body(&page_url).await?;
+            let page: Vec<T> = serde_json::from_str(&body)?;
+            if page.is_empty() {
+                break;
+            }
+            items.extend(page.into_iter().filter(|item| keep(item)));
+            url = link.as_deref().and_then(next_page);
+        }
+        items.truncate(self.max_items);
+        Ok(items)
+    }
+
+    /// GET a response body and its `Link` header, through the cache if there is one. A
+    /// stale cached copy is served when the forge can't be reached or the error is
+    /// transient (see `http_cache::is_transient`).
+    async fn get_body(&self, url: &str) -> Result<(String, Option<String>)> {
+        http_cache::fetch(
+            self.cache.as_ref(),
+            url,
+            |etag| self.get(url, etag),
+            |e| {
+                e.downcast_ref::<reqwest::Error>().is_some()
+                    || e.downcast_ref::<StatusError>().is_some_and(|e| http_cache::is_transient(e.status))
+            },
+        )
+        .await
+    }
+
+    async fn get(&self, url: &str, etag: Option<String>) -> Result<Response> {
+        let mut request = self.client
+            .get(url)
+            .header("User-Agent", "dynamo-mcp")
+            .header("Accept", "application/json");
+        if let Some((header, value, api_url)) = &self.auth {
+            if same_origin(url, api_url) {
+                request = request.header(*header, value);
+            }
+        }
+        if let Some(etag) = &etag {
+            request = request.header("If-None-Match", etag);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() || (status == StatusCode::NOT_MODIFIED && etag.is_some()) {
+            return Ok(response);
+        }
+        Err(StatusError {
+            status,
+            url: url.to_string(),
+        }
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_cache::CacheEntry;
+    use crate::test_support::{etags, paths, serve, Requests, TempDir};
+    use std::time::Duration;
+
+    fn cached(dir: &TempDir, ttl: Duration) -> RestClient {
+        RestClient::new().with_cache(HttpCache::new(dir.path().to_path_buf(), ttl))
+    }
+
+    #[tokio::test]
+    async fn revalidates_with_etag_and_reuses_body_on_not_modified() {
+        let (url, requests) = serve(|_, _, etag, _| match etag {
+            Some("\"v1\"") => (304, vec![], String::new()),
+            _ => (200, vec![("ETag", "\"v1\"".to_string())], "[1, 2]".to_string()),
+        });
+        let dir = TempDir::new("rest-etag");
+        let client = cached(&dir, Duration::ZERO);
+
+        let url = format!("{}/releases", url);
+        for _ in 0..2 {
+            let body: Option<Vec<u32>> = client.get_json(&url).await.unwrap();
+            assert_eq!(body, Some(vec![1, 2]));
+        }
+        assert_eq!(etags(&requests), [None, Some("\"v1\"".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn serves_fresh_entries_without_a_request() {
+        let (url, requests) = serve(|_, _, _, n| (200, vec![], format!("[{}]", n)));
+        let dir = TempDir::new("rest-fresh");
+        let client = cached(&dir, Duration::from_secs(3600));
+
+        let url = format!("{}/releases", url);
+        for _ in 0..2 {
+            let body: Option<Vec<usize>> = client.get_json(&url).await.unwrap();
+            assert_eq!(body, Some(vec![1]));
+        }
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn refetches_entries_older_than_the_ttl() {
+        let (url, requests) = serve(|_, _, _, n| (200, vec![("ETag", format!("\"v{}\"", n))], format!("[{}]", n)));
+        let dir = TempDir::new("rest-expired");
+        let ttl = Duration::from_secs(60);
+        let client = cached(&dir, ttl);
+
+        let url = format!("{}/releases", url);
+        let body: Option<Vec<usize>> = client.get_json(&url).await.unwrap();
+        assert_eq!(body, Some(vec![1]));
+
+        let cache = HttpCache::new(dir.path().to_path_buf(), ttl);
+        let mut entry = cache.load(&url).unwrap();
+        entry.fetched_at -= 120;
+        cache.store(&entry).unwrap();
+
+        let body: Option<Vec<usize>> = client.get_json(&url).await.unwrap();
+        assert_eq!(body, Some(vec![2]));
+        assert_eq!(etags(&requests), [None, Some("\"v1\"".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn serves_stale_entries_on_server_errors_but_not_on_missing_resources() {
+        let (url, _) = serve(|_, _, _, n| match n {
+            1 => (200, vec![("ETag", "\"v1\"".to_string())], "[1]".to_string()),
+            2 => (503, vec![], String::new()),
+            3 => (429, vec![], String::new()),
+            _ => (404, vec![], String::new()),
+        });
+        let dir = TempDir::new("rest-stale");
+        let client = cached(&dir, Duration::ZERO);
+
+        let url = format!("{}/releases", url);
+        for _ in 0..3 {
+            let body: Option<Vec<u32>> = client.get_json(&url).await.unwrap();
+            assert_eq!(body, Some(vec![1]));
+        }
+        let body: Option<Vec<u32>> = client.get_json(&url).await.unwrap();
+        assert_eq!(body, None);
+    }
+
+    #[tokio::test]
+    async fn serves_stale_entries_when_the_forge_is_unreachable() {
+        // Nothing listens on port 1, so connecting fails
+        let url = "http://127.0.0.1:1/releases";
+        assert!(RestClient::new().get_json::<Vec<u32>>(url).await.is_err());
+
+        let dir = TempDir::new("rest-unreachable");
+        let ttl = Duration::from_secs(60);
+        let mut entry = CacheEntry::new(url, None, None, "[7]".to_string());
+        entry.fetched_at -= 3600;
+        HttpCache::new(dir.path().to_path_buf(), ttl).store(&entry).unwrap();
+
+        let body: Option<Vec<u32>> = cached(&dir, ttl).get_json(url).await.unwrap();
+        assert_eq!(body, Some(vec![7]));
+    }
+
+    #[test]
+    fn sends_tokens_only_to_the_api_origin() {
+        assert!(same_origin("https://api.github.com/repos/a/b", "https://api.github.com"));
+        assert!(same_origin("https://gitlab.example/api/v4/projects", "https://gitlab.example/api/v4"));
+        assert!(!same_origin("https://evil.example/repos", "https://api.github.com"));
+        assert!(!same_origin("http://api.github.com/repos", "https://api.github.com"));
+        assert!(!same_origin("https://api.github.com:8443/repos", "https://api.github.com"));
+        assert!(!same_origin("not a url", "https://api.github.com"));
+    }
+
+    /// Three pages of three numbers each, linked with `rel="next"`
+    fn serve_pages() -> (String, Requests) {
+        serve(|base, path, _, _| {
+            let page: usize = path.rsplit('=').next().unwrap().parse().unwrap();
+            let items: Vec<usize> = (1..=3).map(|i| (page - 1) * 3 + i).collect();
+            let mut link = format!("<{}/items?page=3>; rel=\"last\"", base);
+            if page < 3 {
+                link = format!("<{}/items?page={}>; rel=\"next\", {}", base, page + 1, link);
+            }
+            (200, vec![("Link", link)], serde_json::to_string(&items).unwrap())
+        })
+    }
+
+    #[test]
+    fn expands_clone_url_templates_and_base_urls() {
+        assert_eq!(
+            expand_clone_url("https://github.com/{owner}/{repo}.git", "o", "r"),
+            "https://github.com/o/r.git"
+        );
+        assert_eq!(expand_clone_url("file:///srv/mirrors/{repo}", "o", "r"), "file:///srv/mirrors/r");
+        assert_eq!(expand_clone_url("https://mirror.internal/git/", "o", "r"), "https://mirror.internal/git/o/r.git");
+        assert_eq!(expand_clone_url("git@host:{owner}/{repo}.git", "o", "r"), "git@host:o/r.git");
+    }
+
+    #[tokio::test]
+    async fn follows_next_links_through_all_pages() {
+        let (url, requests) = serve_pages();
+        let client = RestClient::new();
+
+        let items: Vec<usize> = client
+            .get_pages(&format!("{}/items?page=1", url), |n: &usize| n.is_multiple_of(2))
+            .await
+            .unwrap();
+        assert_eq!(items, [2, 4, 6, 8]);
+        assert_eq!(paths(&requests), ["/items?page=1", "/items?page=2", "/items?page=3"]);
+    }
+
+    #[tokio::test]
+    async fn stops_paging_once_enough_items_were_kept() {
+        let (url, requests) = serve_pages();
+        let client = RestClient::new().with_max_items(2);
+
+        let items: Vec<usize> = client
+            .get_pages(&format!("{}/items?page=1", url), |n: &usize| n.is_multiple_of(2))
+            .await
+            .unwrap();
+        assert_eq!(items, [2, 4]);
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn caches_each_page_with_its_link() {
+        let (url, requests) = serve_pages();
+        let dir = TempDir::new("rest-pages");
+        let client = cached(&dir, Duration::from_secs(3600));
+
+        let url = format!("{}/items?page=1", url);
+        for _ in 0..2 {
+            let items: Vec<usize> = client.get_pages(&url, |_: &usize| true).await.unwrap();
+            assert_eq!(items, (1..=9).collect::<Vec<_>>());
+        }
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+}
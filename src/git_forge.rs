@@ -0,0 +1,136 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::path::PathBuf;
+use tokio::process::Command;
+
+use crate::forge::{self, Forge, Release};
+use crate::versions::{self, VersionSpec};
+
+/// Releases of a plain git remote, without any forge API: every annotated tag in the
+/// bare clone is a release, and prereleases are told apart by their version.
+pub struct GitForge {
+    bare_path: PathBuf,
+    owner: String,
+    repo: String,
+    clone_url: String,
+}
+
+impl GitForge {
+    pub fn new(bare_path: PathBuf, owner: &str, repo: &str, clone_url: &str) -> Self {
+        Self {
+            bare_path,
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            clone_url: clone_url.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Forge for GitForge {
+    async fn latest_release(&self) -> Result<Option<String>> {
+        let releases = self.list_releases().await?;
+        let tags = releases.iter().map(|r| r.tag_name.as_str());
+        Ok(VersionSpec::Latest.select(tags).map(|t| t.to_string()))
+    }
+
+    async fn list_releases(&self) -> Result<Vec<Release>> {
+        let output = Command::new("git")
+            .args([
+                "for-each-ref",
+                "--sort=-taggerdate",
+                "--format=%(objecttype)%1f%(refname:short)%1f%(taggerdate:iso-strict)%1f%(contents:subject)",
+                "refs/tags",
+            ])
+            .current_dir(&self.bare_path)
+            .output()
+            .await
+            .context("Failed to execute git for-each-ref")?;
+        if !output.status.success() {
+            anyhow::bail!("Failed to list tags: {}", String::from_utf8_lossy(&output.stderr));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('\x1f');
+                // Lightweight tags point directly at a commit
+                if fields.next()? != "tag" {
+                    return None;
+                }
+                let tag_name = fields.next()?.to_string();
+                let published_at = fields.next().filter(|d| !d.is_empty()).map(|d| d.to_string());
+                let name = fields.next().filter(|s| !s.is_empty()).map(|s| s.to_string());
+                let prerelease = versions::parse_tag(&tag_name).is_some_and(|v| !v.pre.is_empty());
+                Some(Release {
+                    tag_name,
+                    name,
+                    published_at,
+                    prerelease,
+                    draft: false,
+                })
+            })
+            .collect())
+    }
+
+    async fn default_branch(&self) -> Result<String> {
+        let output = Command::new("git")
+            .args(["symbolic-ref", "--short", "HEAD"])
+            .current_dir(&self.bare_path)
+            .output()
+            .await
+            .context("Failed to execute git symbolic-ref")?;
+        if !output.status.success() {
+            anyhow::bail!("{:?} has no default branch", self.bare_path);
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn clone_url(&self) -> String {
+        forge::expand_clone_url(&self.clone_url, &self.owner, &self.repo)
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{git, git_at, TempDir};
+
+    #[tokio::test]
+    async fn lists_annotated_tags_as_releases() {
+        let dir = TempDir::new("git-forge");
+        let repo = dir.path();
+        git(repo, &["init", "-q", "-b", "trunk"]);
+        git(repo, &["commit", "-q", "--allow-empty", "-m", "Initial"]);
+        git_at(repo, "2025-01-01T00:00:00Z", &["tag", "-a", "v1.0.0", "-m", "First release"]);
+        git_at(repo, "2025-03-01T00:00:00Z", &["tag", "-a", "v2.0.0-rc.1", "-m", "Release candidate"]);
+        git_at(repo, "2025-02-01T00:00:00Z", &["tag", "-a", "v1.1.0", "-m", "Second release"]);
+        git(repo, &["tag", "v3.0.0"]);
+
+        let forge = GitForge::new(repo.to_path_buf(), "o", "r", "file:///srv/{repo}.git");
+        let releases = forge.list_releases().await.unwrap();
+        let mapped: Vec<(&str, Option<&str>, bool)> = releases
+            .iter()
+            .map(|r| (r.tag_name.as_str(), r.name.as_deref(), r.prerelease))
+            .collect();
+        // Newest first; the lightweight v3.0.0 isn't a release
+        assert_eq!(
+            mapped,
+            [
+                ("v2.0.0-rc.1", Some("Release candidate"), true),
+                ("v1.1.0", Some("Second release"), false),
+                ("v1.0.0", Some("First release"), false),
+            ]
+        );
+        assert!(releases[1].published_at.as_deref().unwrap().starts_with("2025-02-01T00:00:00"));
+
+        assert_eq!(forge.latest_release().await.unwrap().as_deref(), Some("v1.1.0"));
+        assert_eq!(forge.default_branch().await.unwrap(), "trunk");
+        assert_eq!(forge.clone_url(), "file:///srv/r.git");
+        assert!(forge.is_local());
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::forge::{self, Forge, ForgeConfig, Release, RestClient};
+
+/// API used unless a repository says otherwise
+pub const DEFAULT_API_URL: &str = "https://gitea.com/api/v1";
+
+/// Clone URL used unless a repository says otherwise
+pub const DEFAULT_CLONE_URL: &str = "https://gitea.com/{owner}/{repo}.git";
+
+#[derive(Debug, Deserialize)]
+struct GiteaRepo {
+    default_branch: String,
+}
+
+/// Releases of a repository on Gitea or Forgejo (e.g. Codeberg, with `api_url` set
+/// to `https://codeberg.org/api/v1`). Requests are authenticated with `GITEA_TOKEN`
+/// when it is set.
+pub struct GiteaClient {
+    rest: RestClient,
+    owner: String,
+    repo: String,
+    api_url: String,
+    clone_url: String,
+}
+
+impl GiteaClient {
+    pub fn new(owner: &str, repo: &str, config: &ForgeConfig, rest: RestClient) -> Self {
+        let api_url = config.api_url.clone().unwrap_or_else(|| DEFAULT_API_URL.to_string());
+        let rest = match forge::token("GITEA_TOKEN") {
+            Some(token) => rest.with_auth("Authorization", format!("token {}", token), &api_url),
+            None => rest,
+        };
+        Self {
+            rest,
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            api_url,
+            clone_url: config.clone_url.clone().unwrap_or_else(|| DEFAULT_CLONE_URL.to_string()),
+        }
+    }
+
+    fn repo_url(&self) -> String {
+        format!(
+            "{}/repos/{}/{}",
+            self.api_url.trim_end_matches('/'),
+            self.owner,
+            self.repo
+        )
+    }
+}
+
+#[async_trait]
+impl Forge for GiteaClient {
+    async fn latest_release(&self) -> Result<Option<String>> {
+        let url = format!("{}/releases/latest", self.repo_url());
+        let release: Option<Release> = self.rest.get_json(&url).await?;
+        match release {
+            Some(release) => {
+                info!("Found latest release: {}", release.tag_name);
+                Ok(Some(release.tag_name))
+            }
+            None => {
+                info!("No releases found for {}/{}", self.owner, self.repo);
+                Ok(None)
+            }
+        }
+    }
+
+    async fn list_releases(&self) -> Result<Vec<Release>> {
+        // Gitea caps pages at 50 items by default
+        let url = format!("{}/releases?limit=50", self.repo_url());
+        self.rest.get_pages(&url, |r: &Release| !r.draft).await
+    }
+
+    async fn default_branch(&self) -> Result<String> {
+        let repo: Option<GiteaRepo> = self.rest.get_json(&self.repo_url()).await?;
+        repo.map(|r| r.default_branch)
+            .ok_or_else(|| anyhow::anyhow!("Gitea repository {}/{} not found", self.owner, self.repo))
+    }
+
+    fn clone_url(&self) -> String {
+        forge::expand_clone_url(&self.clone_url, &self.owner, &self.repo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{paths, serve};
+
+    #[tokio::test]
+    async fn maps_releases_and_skips_drafts() {
+        let (url, requests) = serve(|_, path, _, _| {
+            let body = match path {
+                "/repos/o/r/releases/latest" => serde_json::json!({
+                    "tag_name": "v1.0.0", "name": "First", "published_at": "2025-01-01T00:00:00Z",
+                    "prerelease": false, "draft": false,
+                }),
+                "/repos/o/r" => serde_json::json!({"default_branch": "trunk"}),
+                _ => serde_json::json!([
+                    {"tag_name": "v2.0.0", "name": "", "published_at": null, "prerelease": false, "draft": true},
+                    {"tag_name": "v1.1.0", "name": "Beta", "published_at": "2025-06-01T00:00:00Z", "prerelease": true, "draft": false},
+                    {"tag_name": "v1.0.0", "name": "First", "published_at": "2025-01-01T00:00:00Z", "prerelease": false, "draft": false},
+                ]),
+            };
+            (200, vec![], body.to_string())
+        });
+        let config = ForgeConfig {
+            api_url: Some(format!("{}/", url)),
+            ..Default::default()
+        };
+        let client = GiteaClient::new("o", "r", &config, RestClient::new());
+
+        let releases = client.list_releases().await.unwrap();
+        let mapped: Vec<(&str, bool)> = releases.iter().map(|r| (r.tag_name.as_str(), r.prerelease)).collect();
+        assert_eq!(mapped, [("v1.1.0", true), ("v1.0.0", false)]);
+        assert_eq!(releases[0].name.as_deref(), Some("Beta"));
+        assert_eq!(client.latest_release().await.unwrap().as_deref(), Some("v1.0.0"));
+        assert_eq!(client.default_branch().await.unwrap(), "trunk");
+        assert_eq!(
+            paths(&requests),
+            ["/repos/o/r/releases?limit=50", "/repos/o/r/releases/latest", "/repos/o/r"]
+        );
+    }
+
+    #[tokio::test]
+    async fn has_no_latest_release_when_none_was_published() {
+        let (url, _) = serve(|_, _, _, _| (404, vec![], String::new()));
+        let config = ForgeConfig {
+            api_url: Some(url),
+            ..Default::default()
+        };
+        let client = GiteaClient::new("o", "r", &config, RestClient::new());
+        assert_eq!(client.latest_release().await.unwrap(), None);
+        assert!(client.default_branch().await.is_err());
+    }
+}
@@ -1,119 +1,647 @@
-use anyhow::Result;
+use once_cell::sync::Lazy;
+use async_trait::async_trait;
+use reqwest::Response;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use thiserror::Error;
 use tracing::{info, warn};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct GitHubRelease {
-    pub tag_name: String,
-    pub name: Option<String>,
-    pub published_at: String,
-    pub prerelease: bool,
-    pub draft: bool,
-}
+use crate::forge::{self, Forge, ForgeConfig, Release};
+use crate::http_cache::{self, next_page, HttpCache};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GitHubRepo {
     pub default_branch: String,
 }
 
+/// Rate limit window reported in the `X-RateLimit-*` response headers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub limit: u64,
+    pub remaining: u64,
+    #[serde(default)]
+    pub used: u64,
+    /// Unix time at which the window resets
+    pub reset: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource: Option<String>,
+}
+
+impl RateLimit {
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| headers.get(name)?.to_str().ok();
+        Some(Self {
+            limit: header("x-ratelimit-limit")?.parse().ok()?,
+            remaining: header("x-ratelimit-remaining")?.parse().ok()?,
+            used: header("x-ratelimit-used")
+                .and_then(|used| used.parse().ok())
+                .unwrap_or_default(),
+            reset: header("x-ratelimit-reset")?.parse().ok()?,
+            resource: header("x-ratelimit-resource").map(|r| r.to_string()),
+        })
+    }
+
+    /// When the window resets, in RFC 3339
+    pub fn reset_at(&self) -> String {
+        chrono::DateTime::from_timestamp(self.reset, 0)
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum GitHubError {
+    #[error("GitHub API rate limit exceeded{}", rate_limit_hint(.rate_limit, .retry_after))]
+    RateLimited {
+        rate_limit: Option<RateLimit>,
+        /// Seconds to wait before retrying, sent with secondary rate limits
+        retry_after: Option<u64>,
+    },
+    #[error("Not found on GitHub: {0}")]
+    NotFound(String),
+    #[error("GitHub rejected the token in GITHUB_TOKEN")]
+    Unauthorized,
+    #[error("GitHub API returned {status} for {url}")]
+    Status { status: StatusCode, url: String },
+    #[error("GitHub request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Unexpected GitHub API response: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+fn rate_limit_hint(rate_limit: &Option<RateLimit>, retry_after: &Option<u64>) -> String {
+    let mut hint = String::new();
+    if let Some(rate_limit) = rate_limit {
+        hint.push_str(&format!(
+            " ({} requests per hour, resets at {})",
+            rate_limit.limit,
+            rate_limit.reset_at()
+        ));
+    }
+    if let Some(seconds) = retry_after {
+        hint.push_str(&format!(", retry after {}s", seconds));
+    }
+    if token().is_none() {
+        hint.push_str("; set GITHUB_TOKEN for a higher limit");
+    }
+    hint
+}
+
+/// API used unless a repository or `GITHUB_API_URL` says otherwise
+pub const DEFAULT_API_URL: &str = "https://api.github.com";
+
+/// Clone URL used unless a repository says otherwise
+pub const DEFAULT_CLONE_URL: &str = "https://github.com/{owner}/{repo}.git";
+
+/// Largest page size the API allows
+const RELEASES_PER_PAGE: usize = 100;
+
+/// Rate limit seen on the most recent GitHub response, shared by all clients
+static LAST_RATE_LIMIT: Lazy<Mutex<Option<RateLimit>>> = Lazy::new(|| Mutex::new(None));
+
+/// Rate limit reported by the most recent GitHub response, if any request was made
+pub fn last_rate_limit() -> Option<RateLimit> {
+    LAST_RATE_LIMIT.lock().unwrap().clone()
+}
+
+/// Token from `GITHUB_TOKEN`, if set and not blank
+pub fn token() -> Option<String> {
+    forge::token("GITHUB_TOKEN")
+}
+
 pub struct GitHubClient {
-    client: reqwest::blocking::Client,
+    client: reqwest::Client,
     owner: String,
     repo: String,
+    api_url: String,
+    clone_url: String,
+    token: Option<String>,
+    cache: Option<HttpCache>,
+    max_releases: usize,
 }
 
 impl GitHubClient {
     pub fn new(owner: impl Into<String>, repo: impl Into<String>) -> Self {
         Self {
-            client: reqwest::blocking::Client::new(),
+            client: reqwest::Client::new(),
             owner: owner.into(),
             repo: repo.into(),
+            api_url: std::env::var("GITHUB_API_URL")
+                .unwrap_or_else(|_| DEFAULT_API_URL.to_string()),
+            clone_url: DEFAULT_CLONE_URL.to_string(),
+            token: token(),
+            cache: None,
+            max_releases: forge::DEFAULT_MAX_RELEASES,
+        }
+    }
+
+    /// Stop paging through releases once this many were fetched
+    pub fn with_max_releases(mut self, max_releases: usize) -> Self {
+        self.max_releases = max_releases;
+        self
+    }
+
+    /// Use a repository's configured API and clone URLs instead of the defaults
+    pub fn with_config(mut self, config: &ForgeConfig) -> Self {
+        if let Some(api_url) = &config.api_url {
+            self.api_url = api_url.clone();
         }
+        if let Some(clone_url) = &config.clone_url {
+            self.clone_url = clone_url.clone();
+        }
+        self
+    }
+
+    /// Serve responses from an on-disk cache, revalidating stale entries with
+    /// conditional requests (which don't count against the rate limit when unchanged)
+    pub fn with_cache(mut self, cache: HttpCache) -> Self {
+        self.cache = Some(cache);
+        self
     }
 
-    /// Get the latest release tag from GitHub
-    pub fn get_latest_release(&self) -> Result<Option<String>> {
+    /// Get the latest release tag from GitHub, or None if there are no releases
+    pub async fn get_latest_release(&self) -> Result<Option<String>, GitHubError> {
         let url = format!(
-            "https://api.github.com/repos/{}/{}/releases/latest",
-            self.owner, self.repo
+            "{}/repos/{}/{}/releases/latest",
+            self.api_url(), self.owner, self.repo
         );
 
-        let response = self.client
-            .get(&url)
-            .header("User-Agent", "dynamo-mcp")
-            .send();
-
-        match response {
-            Ok(resp) if resp.status().is_success() => {
-                let release: GitHubRelease = resp.json()?;
+        match self.get_json::<Release>(&url).await {
+            Ok(release) => {
                 info!("Found latest release: {}", release.tag_name);
                 Ok(Some(release.tag_name))
             }
-            Ok(resp) if resp.status() == 404 => {
+            Err(GitHubError::NotFound(_)) => {
                 info!("No releases found for {}/{}", self.owner, self.repo);
                 Ok(None)
             }
-            Ok(resp) => {
-                warn!("GitHub API error: {}", resp.status());
-                Ok(None)
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get published (non-draft) releases from GitHub, newest first, following the
+    /// `Link` header through all pages until `max_releases` published ones are fetched
+    pub async fn list_releases(&self) -> Result<Vec<Release>, GitHubError> {
+        let mut url = Some(format!(
+            "{}/repos/{}/{}/releases?per_page={}",
+            self.api_url(), self.owner, self.repo, RELEASES_PER_PAGE
+        ));
+
+        let mut releases: Vec<Release> = Vec::new();
+        while let Some(page_url) = url.take() {
+            if releases.len() >= self.max_releases {
+                info!("Stopped listing releases of {}/{} at {}", self.owner, self.repo, self.max_releases);
+                break;
             }
-            Err(e) => {
-                warn!("Failed to fetch release: {}", e);
-                Ok(None)
+            let (body, link) = self.get_body(&page_url).await?;
+            let page: Vec<Release> = serde_json::from_str(&body)?;
+            if page.is_empty() {
+                break;
             }
+            releases.extend(page.into_iter().filter(|r| !r.draft));
+            url = link.as_deref().and_then(next_page);
         }
+        releases.truncate(self.max_releases);
+        Ok(releases)
     }
 
-    /// Get all releases from GitHub
-    pub fn list_releases(&self) -> Result<Vec<String>> {
+    /// Get the default branch name
+    pub async fn get_default_branch(&self) -> Result<String, GitHubError> {
         let url = format!(
-            "https://api.github.com/repos/{}/{}/releases",
-            self.owner, self.repo
+            "{}/repos/{}/{}",
+            self.api_url(), self.owner, self.repo
         );
 
-        let response = self.client
-            .get(&url)
+        let repo: GitHubRepo = self.get_json(&url).await?;
+        Ok(repo.default_branch)
+    }
+
+    /// Query the core API rate limit. Doesn't count against the limit itself.
+    pub async fn rate_limit(&self) -> Result<RateLimit, GitHubError> {
+        #[derive(Deserialize)]
+        struct Resources {
+            core: RateLimit,
+        }
+
+        #[derive(Deserialize)]
+        struct RateLimitResponse {
+            resources: Resources,
+        }
+
+        let response: RateLimitResponse = self
+            .get(&format!("{}/rate_limit", self.api_url()), None)
+            .await?
+            .json()
+            .await?;
+        let mut core = response.resources.core;
+        core.resource.get_or_insert_with(|| "core".to_string());
+        Ok(core)
+    }
+
+    /// Get the clone URL for the repository
+    pub fn clone_url(&self) -> String {
+        forge::expand_clone_url(&self.clone_url, &self.owner, &self.repo)
+    }
+
+    fn api_url(&self) -> &str {
+        self.api_url.trim_end_matches('/')
+    }
+
+    /// GET a JSON document, through the cache if there is one
+    async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T, GitHubError> {
+        let (body, _) = self.get_body(url).await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    /// GET a response body and its `Link` header, through the cache if there is one. A
+    /// stale cached copy is served when GitHub can't be reached, the rate limit is
+    /// exhausted or the error is otherwise transient (see `http_cache::is_transient`).
+    async fn get_body(&self, url: &str) -> Result<(String, Option<String>), GitHubError> {
+        http_cache::fetch(self.cache.as_ref(), url, |etag| self.get(url, etag), |e| match e {
+            GitHubError::RateLimited { .. } | GitHubError::Http(_) => true,
+            GitHubError::Status { status, .. } => http_cache::is_transient(*status),
+            _ => false,
+        })
+        .await
+    }
+
+    /// Send a GET request, authenticated when `GITHUB_TOKEN` is set and the URL is on
+    /// the configured API, and conditional when an ETag is given. Records the rate limit
+    /// headers and turns unsuccessful responses into typed errors; `304 Not Modified` is
+    /// passed through.
+    async fn get(&self, url: &str, etag: Option<String>) -> Result<Response, GitHubError> {
+        let mut request = self.client
+            .get(url)
             .header("User-Agent", "dynamo-mcp")
-            .send();
-
-        match response {
-            Ok(resp) if resp.status().is_success() => {
-                let releases: Vec<GitHubRelease> = resp.json()?;
-                Ok(releases.into_iter()
-                    .filter(|r| !r.draft)
-                    .map(|r| r.tag_name)
-                    .collect())
+            .header("Accept", "application/vnd.github+json");
+        if let Some(token) = self.token.as_ref().filter(|_| forge::same_origin(url, &self.api_url)) {
+            request = request.bearer_auth(token);
+        }
+        if let Some(etag) = &etag {
+            request = request.header("If-None-Match", etag);
+        }
+
+        let response = request.send().await?;
+        let rate_limit = RateLimit::from_headers(response.headers());
+        if let Some(rate_limit) = &rate_limit {
+            *LAST_RATE_LIMIT.lock().unwrap() = Some(rate_limit.clone());
+        }
+
+        let status = response.status();
+        if status.is_success() || (status == StatusCode::NOT_MODIFIED && etag.is_some()) {
+            return Ok(response);
+        }
+
+        let error = error_for(status, response.headers(), url);
+        if !matches!(error, GitHubError::NotFound(_)) {
+            warn!("{}", error);
+        }
+        Err(error)
+    }
+}
+
+/// Classify an unsuccessful response. GitHub answers 403 or 429 when a rate limit is
+/// hit, telling them apart from permission errors by an exhausted limit or a
+/// `Retry-After` header.
+fn error_for(status: StatusCode, headers: &HeaderMap, url: &str) -> GitHubError {
+    let rate_limit = RateLimit::from_headers(headers);
+    let retry_after = headers
+        .get("retry-after")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+    let exhausted = rate_limit.as_ref().is_some_and(|r| r.remaining == 0);
+    match status {
+        StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS if exhausted || retry_after.is_some() => {
+            GitHubError::RateLimited {
+                rate_limit,
+                retry_after,
             }
-            _ => Ok(Vec::new())
         }
+        StatusCode::NOT_FOUND => GitHubError::NotFound(url.to_string()),
+        StatusCode::UNAUTHORIZED => GitHubError::Unauthorized,
+        _ => GitHubError::Status {
+            status,
+            url: url.to_string(),
+        },
     }
+}
 
-    /// Get the default branch name
-    pub fn get_default_branch(&self) -> Result<String> {
-        let url = format!(
-            "https://api.github.com/repos/{}/{}",
-            self.owner, self.repo
+#[async_trait]
+impl Forge for GitHubClient {
+    async fn latest_release(&self) -> anyhow::Result<Option<String>> {
+        Ok(self.get_latest_release().await?)
+    }
+
+    async fn list_releases(&self) -> anyhow::Result<Vec<Release>> {
+        Ok(GitHubClient::list_releases(self).await?)
+    }
+
+    async fn default_branch(&self) -> anyhow::Result<String> {
+        Ok(self.get_default_branch().await?)
+    }
+
+    fn clone_url(&self) -> String {
+        GitHubClient::clone_url(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_cache::CacheEntry;
+    use crate::test_support::{etags, paths, serve, TempDir};
+    use std::time::Duration;
+
+    /// Client for `o/r` on a test server, caching responses in `dir`
+    fn client(api_url: &str, dir: &TempDir, ttl: Duration) -> GitHubClient {
+        let config = ForgeConfig {
+            api_url: Some(api_url.to_string()),
+            ..Default::default()
+        };
+        GitHubClient::new("o", "r")
+            .with_config(&config)
+            .with_cache(HttpCache::new(dir.path().to_path_buf(), ttl))
+    }
+
+    /// `/releases` response listing the given tags
+    fn releases(tags: &[&str]) -> String {
+        let releases: Vec<serde_json::Value> = tags
+            .iter()
+            .map(|tag| {
+                serde_json::json!({
+                    "tag_name": tag,
+                    "name": null,
+                    "published_at": "2025-06-01T00:00:00Z",
+                    "prerelease": false,
+                    "draft": false,
+                })
+            })
+            .collect();
+        serde_json::to_string(&releases).unwrap()
+    }
+
+    fn tags(releases: &[Release]) -> Vec<&str> {
+        releases.iter().map(|r| r.tag_name.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn revalidates_with_etags_and_reuses_the_body_when_not_modified() {
+        let (url, requests) = serve(|_, _, etag, _| match etag {
+            Some("\"v1\"") => (304, vec![], String::new()),
+            _ => (200, vec![("ETag", "\"v1\"".to_string())], releases(&["v1.0.0"])),
+        });
+        let dir = TempDir::new("github-etag");
+        let client = client(&url, &dir, Duration::ZERO);
+
+        for _ in 0..2 {
+            assert_eq!(tags(&client.list_releases().await.unwrap()), ["v1.0.0"]);
+        }
+        assert_eq!(etags(&requests), [None, Some("\"v1\"".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn serves_fresh_responses_without_a_request_across_restarts() {
+        let (url, requests) = serve(|_, _, _, n| (200, vec![], releases(&[&format!("v{}", n)])));
+        let dir = TempDir::new("github-fresh");
+        let ttl = Duration::from_secs(3600);
+
+        assert_eq!(tags(&client(&url, &dir, ttl).list_releases().await.unwrap()), ["v1"]);
+        assert_eq!(tags(&client(&url, &dir, ttl).list_releases().await.unwrap()), ["v1"]);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn refetches_responses_older_than_the_ttl() {
+        let (url, requests) = serve(|_, _, _, n| {
+            (200, vec![("ETag", format!("\"v{}\"", n))], releases(&[&format!("v{}", n)]))
+        });
+        let dir = TempDir::new("github-expired");
+        let ttl = Duration::from_secs(60);
+        let client = client(&url, &dir, ttl);
+        assert_eq!(tags(&client.list_releases().await.unwrap()), ["v1"]);
+
+        let cache = HttpCache::new(dir.path().to_path_buf(), ttl);
+        let page_url = format!("{}/repos/o/r/releases?per_page={}", url, RELEASES_PER_PAGE);
+        let mut entry = cache.load(&page_url).unwrap();
+        entry.fetched_at -= 120;
+        cache.store(&entry).unwrap();
+
+        assert_eq!(tags(&client.list_releases().await.unwrap()), ["v2"]);
+        assert_eq!(etags(&requests), [None, Some("\"v1\"".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn serves_stale_responses_when_rate_limited_but_not_when_missing() {
+        let (url, _) = serve(|_, _, _, n| match n {
+            1 => (200, vec![("ETag", "\"v1\"".to_string())], releases(&["v1.0.0"])),
+            2 => (
+                403,
+                vec![
+                    ("x-ratelimit-limit", "60".to_string()),
+                    ("x-ratelimit-remaining", "0".to_string()),
+                    ("x-ratelimit-reset", "1750000000".to_string()),
+                ],
+                String::new(),
+            ),
+            _ => (404, vec![], String::new()),
+        });
+        let dir = TempDir::new("github-stale");
+        let client = client(&url, &dir, Duration::ZERO);
+
+        for _ in 0..2 {
+            assert_eq!(tags(&client.list_releases().await.unwrap()), ["v1.0.0"]);
+        }
+        assert!(matches!(client.list_releases().await, Err(GitHubError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn serves_stale_responses_on_server_errors() {
+        let (url, _) = serve(|_, _, _, n| match n {
+            1 => (200, vec![], releases(&["v1.0.0"])),
+            2 => (502, vec![], String::new()),
+            3 => (429, vec![], String::new()),
+            _ => (400, vec![], String::new()),
+        });
+        let dir = TempDir::new("github-server-error");
+        let client = client(&url, &dir, Duration::ZERO);
+
+        for _ in 0..3 {
+            assert_eq!(tags(&client.list_releases().await.unwrap()), ["v1.0.0"]);
+        }
+        assert!(matches!(
+            client.list_releases().await,
+            Err(GitHubError::Status { status: StatusCode::BAD_REQUEST, .. })
+        ));
+    }
+
+    /// Releases `v1`..`v9` over three pages linked with `rel="next"`, where every
+    /// third release is a draft
+    fn serve_release_pages() -> (String, crate::test_support::Requests) {
+        serve(|base, path, _, _| {
+            let page: usize = path.split_once("&page=").map_or(1, |(_, page)| page.parse().unwrap());
+            let releases: Vec<serde_json::Value> = (1..=3)
+                .map(|i| {
+                    let n = (page - 1) * 3 + i;
+                    serde_json::json!({
+                        "tag_name": format!("v{}", n),
+                        "name": null,
+                        "published_at": null,
+                        "prerelease": false,
+                        "draft": n.is_multiple_of(3),
+                    })
+                })
+                .collect();
+            let path = "/repos/o/r/releases?per_page=100&page=";
+            let mut link = format!("<{}{}3>; rel=\"last\"", base, path);
+            if page < 3 {
+                link = format!("<{}{}{}>; rel=\"next\", {}", base, path, page + 1, link);
+            }
+            (200, vec![("Link", link)], serde_json::to_string(&releases).unwrap())
+        })
+    }
+
+    #[tokio::test]
+    async fn lists_releases_across_pages_without_drafts() {
+        let (url, requests) = serve_release_pages();
+        let dir = TempDir::new("github-pages");
+        let client = client(&url, &dir, Duration::ZERO);
+
+        let releases = client.list_releases().await.unwrap();
+        assert_eq!(tags(&releases), ["v1", "v2", "v4", "v5", "v7", "v8"]);
+        assert_eq!(
+            paths(&requests),
+            [
+                "/repos/o/r/releases?per_page=100",
+                "/repos/o/r/releases?per_page=100&page=2",
+                "/repos/o/r/releases?per_page=100&page=3",
+            ]
         );
+    }
 
-        let response = self.client
-            .get(&url)
-            .header("User-Agent", "dynamo-mcp")
-            .send();
+    #[tokio::test]
+    async fn stops_listing_releases_at_the_cap_not_counting_drafts() {
+        let (url, requests) = serve_release_pages();
+        let dir = TempDir::new("github-cap");
 
-        match response {
-            Ok(resp) if resp.status().is_success() => {
-                let repo: GitHubRepo = resp.json()?;
-                Ok(repo.default_branch)
+        // The first page has only two published releases, so a cap of three needs two pages
+        let three = client(&url, &dir, Duration::ZERO).with_max_releases(3);
+        assert_eq!(tags(&three.list_releases().await.unwrap()), ["v1", "v2", "v4"]);
+        assert_eq!(requests.lock().unwrap().len(), 2);
+
+        let two = client(&url, &dir, Duration::ZERO).with_max_releases(2);
+        assert_eq!(tags(&two.list_releases().await.unwrap()), ["v1", "v2"]);
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn serves_stale_responses_when_github_is_unreachable() {
+        // Nothing listens on port 1, so connecting fails
+        let url = "http://127.0.0.1:1";
+        let dir = TempDir::new("github-unreachable");
+        let ttl = Duration::from_secs(60);
+        assert!(matches!(
+            client(url, &dir, ttl).list_releases().await,
+            Err(GitHubError::Http(_))
+        ));
+
+        let page_url = format!("{}/repos/o/r/releases?per_page={}", url, RELEASES_PER_PAGE);
+        let mut entry = CacheEntry::new(&page_url, None, None, releases(&["v0.9.0"]));
+        entry.fetched_at -= 3600;
+        HttpCache::new(dir.path().to_path_buf(), ttl).store(&entry).unwrap();
+        assert_eq!(tags(&client(url, &dir, ttl).list_releases().await.unwrap()), ["v0.9.0"]);
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn reads_rate_limits_from_headers() {
+        let rate_limit = RateLimit::from_headers(&headers(&[
+            ("x-ratelimit-limit", "5000"),
+            ("x-ratelimit-remaining", "4990"),
+            ("x-ratelimit-used", "10"),
+            ("x-ratelimit-reset", "1750000000"),
+            ("x-ratelimit-resource", "core"),
+        ]))
+        .unwrap();
+        assert_eq!((rate_limit.limit, rate_limit.remaining, rate_limit.used), (5000, 4990, 10));
+        assert_eq!(rate_limit.resource.as_deref(), Some("core"));
+        assert_eq!(rate_limit.reset_at(), "2025-06-15T15:06:40+00:00");
+
+        // `used` and `resource` are optional, the rest is required and numeric
+        let minimal = RateLimit::from_headers(&headers(&[
+            ("x-ratelimit-limit", "60"),
+            ("x-ratelimit-remaining", "0"),
+            ("x-ratelimit-reset", "1750000000"),
+        ]))
+        .unwrap();
+        assert_eq!((minimal.used, minimal.resource), (0, None));
+        assert!(RateLimit::from_headers(&headers(&[("x-ratelimit-limit", "60")])).is_none());
+        assert!(RateLimit::from_headers(&headers(&[
+            ("x-ratelimit-limit", "60"),
+            ("x-ratelimit-remaining", "many"),
+            ("x-ratelimit-reset", "1750000000"),
+        ]))
+        .is_none());
+    }
+
+    #[test]
+    fn classifies_error_responses() {
+        let url = "https://api.github.com/repos/o/r";
+        let exhausted = headers(&[
+            ("x-ratelimit-limit", "60"),
+            ("x-ratelimit-remaining", "0"),
+            ("x-ratelimit-reset", "1750000000"),
+        ]);
+        match error_for(StatusCode::FORBIDDEN, &exhausted, url) {
+            GitHubError::RateLimited { rate_limit, retry_after } => {
+                assert_eq!(rate_limit.unwrap().limit, 60);
+                assert_eq!(retry_after, None);
             }
-            _ => {
-                // Fallback to main
-                Ok("main".to_string())
+            e => panic!("unexpected {:?}", e),
+        }
+
+        // Secondary rate limits say when to retry instead
+        let secondary = headers(&[("retry-after", "30")]);
+        match error_for(StatusCode::TOO_MANY_REQUESTS, &secondary, url) {
+            GitHubError::RateLimited { rate_limit, retry_after } => {
+                assert!(rate_limit.is_none());
+                assert_eq!(retry_after, Some(30));
             }
+            e => panic!("unexpected {:?}", e),
         }
-    }
+        let error = error_for(StatusCode::FORBIDDEN, &secondary, url).to_string();
+        assert!(error.contains("retry after 30s"), "{}", error);
 
-    /// Get the clone URL for the repository
-    pub fn clone_url(&self) -> String {
-        format!("https://github.com/{}/{}.git", self.owner, self.repo)
+        // A 403 with requests left is a permission error
+        let remaining = headers(&[
+            ("x-ratelimit-limit", "60"),
+            ("x-ratelimit-remaining", "59"),
+            ("x-ratelimit-reset", "1750000000"),
+        ]);
+        assert!(matches!(
+            error_for(StatusCode::FORBIDDEN, &remaining, url),
+            GitHubError::Status { status: StatusCode::FORBIDDEN, .. }
+        ));
+        assert!(matches!(
+            error_for(StatusCode::NOT_FOUND, &exhausted, url),
+            GitHubError::NotFound(u) if u == url
+        ));
+        assert!(matches!(
+            error_for(StatusCode::UNAUTHORIZED, &HeaderMap::new(), url),
+            GitHubError::Unauthorized
+        ));
+        assert!(matches!(
+            error_for(StatusCode::BAD_GATEWAY, &HeaderMap::new(), url),
+            GitHubError::Status { status: StatusCode::BAD_GATEWAY, .. }
+        ));
     }
-}
\ No newline at end of file
+}
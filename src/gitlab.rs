@@ -0,0 +1,188 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::forge::{self, Forge, ForgeConfig, Release, RestClient};
+use crate::versions;
+
+/// API used unless a repository says otherwise
+pub const DEFAULT_API_URL: &str = "https://gitlab.com/api/v4";
+
+/// Clone URL used unless a repository says otherwise
+pub const DEFAULT_CLONE_URL: &str = "https://gitlab.com/{owner}/{repo}.git";
+
+#[derive(Debug, Deserialize)]
+struct GitLabRelease {
+    tag_name: String,
+    name: Option<String>,
+    released_at: Option<String>,
+    /// Scheduled for a future date
+    #[serde(default)]
+    upcoming_release: bool,
+}
+
+impl From<GitLabRelease> for Release {
+    /// GitLab has no prerelease flag, so prereleases are told apart by their version
+    fn from(release: GitLabRelease) -> Self {
+        let prerelease = versions::parse_tag(&release.tag_name).is_some_and(|v| !v.pre.is_empty());
+        Self {
+            tag_name: release.tag_name,
+            name: release.name,
+            published_at: release.released_at,
+            prerelease,
+            draft: false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GitLabProject {
+    default_branch: Option<String>,
+}
+
+/// Releases of a GitLab project, where `owner` is its (possibly nested) group.
+/// Requests are authenticated with `GITLAB_TOKEN` when it is set.
+pub struct GitLabClient {
+    rest: RestClient,
+    owner: String,
+    repo: String,
+    api_url: String,
+    clone_url: String,
+}
+
+impl GitLabClient {
+    pub fn new(owner: &str, repo: &str, config: &ForgeConfig, rest: RestClient) -> Self {
+        let api_url = config.api_url.clone().unwrap_or_else(|| DEFAULT_API_URL.to_string());
+        let rest = match forge::token("GITLAB_TOKEN") {
+            Some(token) => rest.with_auth("PRIVATE-TOKEN", token, &api_url),
+            None => rest,
+        };
+        Self {
+            rest,
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            api_url,
+            clone_url: config.clone_url.clone().unwrap_or_else(|| DEFAULT_CLONE_URL.to_string()),
+        }
+    }
+
+    /// API URL of the project, addressed by its URL-encoded path (`group%2Fproject`)
+    fn project_url(&self) -> String {
+        format!(
+            "{}/projects/{}",
+            self.api_url.trim_end_matches('/'),
+            encode_segment(&format!("{}/{}", self.owner, self.repo))
+        )
+    }
+}
+
+/// Percent-encode everything but unreserved characters, so a path fits in one URL segment
+fn encode_segment(path: &str) -> String {
+    path.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[async_trait]
+impl Forge for GitLabClient {
+    async fn latest_release(&self) -> Result<Option<String>> {
+        let url = format!("{}/releases/permalink/latest", self.project_url());
+        let release: Option<GitLabRelease> = self.rest.get_json(&url).await?;
+        match release {
+            Some(release) => {
+                info!("Found latest release: {}", release.tag_name);
+                Ok(Some(release.tag_name))
+            }
+            None => {
+                info!("No releases found for {}/{}", self.owner, self.repo);
+                Ok(None)
+            }
+        }
+    }
+
+    async fn list_releases(&self) -> Result<Vec<Release>> {
+        let url = format!("{}/releases?per_page=100", self.project_url());
+        let releases = self.rest.get_pages(&url, |r: &GitLabRelease| !r.upcoming_release).await?;
+        Ok(releases.into_iter().map(Release::from).collect())
+    }
+
+    async fn default_branch(&self) -> Result<String> {
+        let project: Option<GitLabProject> = self.rest.get_json(&self.project_url()).await?;
+        project
+            .ok_or_else(|| anyhow::anyhow!("GitLab project {}/{} not found", self.owner, self.repo))?
+            .default_branch
+            .ok_or_else(|| anyhow::anyhow!("GitLab project {}/{} has no default branch", self.owner, self.repo))
+    }
+
+    fn clone_url(&self) -> String {
+        forge::expand_clone_url(&self.clone_url, &self.owner, &self.repo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{paths, serve};
+
+    fn client(api_url: &str, owner: &str) -> GitLabClient {
+        let config = ForgeConfig {
+            api_url: Some(api_url.to_string()),
+            ..Default::default()
+        };
+        GitLabClient::new(owner, "docs.site", &config, RestClient::new())
+    }
+
+    #[test]
+    fn addresses_projects_by_their_encoded_path() {
+        assert_eq!(
+            client("https://gitlab.example/api/v4/", "group").project_url(),
+            "https://gitlab.example/api/v4/projects/group%2Fdocs.site"
+        );
+        assert_eq!(
+            client("https://gitlab.example/api/v4", "group/sub group").project_url(),
+            "https://gitlab.example/api/v4/projects/group%2Fsub%20group%2Fdocs.site"
+        );
+    }
+
+    #[tokio::test]
+    async fn maps_releases_and_skips_upcoming_ones() {
+        let (url, requests) = serve(|_, path, _, _| {
+            if path.ends_with("/releases/permalink/latest") {
+                return (404, vec![], String::new());
+            }
+            let body = serde_json::json!([
+                {"tag_name": "v2.0.0", "name": "Next", "released_at": "2030-01-01T00:00:00Z", "upcoming_release": true},
+                {"tag_name": "v1.1.0-rc.1", "name": null, "released_at": "2025-06-01T00:00:00Z"},
+                {"tag_name": "v1.0.0", "name": "First", "released_at": "2025-01-01T00:00:00Z", "upcoming_release": false},
+            ]);
+            (200, vec![], body.to_string())
+        });
+        let client = client(&url, "group/sub");
+
+        let releases = client.list_releases().await.unwrap();
+        let mapped: Vec<(&str, Option<&str>, Option<&str>, bool)> = releases
+            .iter()
+            .map(|r| (r.tag_name.as_str(), r.name.as_deref(), r.published_at.as_deref(), r.prerelease))
+            .collect();
+        assert_eq!(
+            mapped,
+            [
+                ("v1.1.0-rc.1", None, Some("2025-06-01T00:00:00Z"), true),
+                ("v1.0.0", Some("First"), Some("2025-01-01T00:00:00Z"), false),
+            ]
+        );
+        assert!(releases.iter().all(|r| !r.draft));
+        assert_eq!(client.latest_release().await.unwrap(), None);
+        assert_eq!(
+            paths(&requests),
+            [
+                "/projects/group%2Fsub%2Fdocs.site/releases?per_page=100",
+                "/projects/group%2Fsub%2Fdocs.site/releases/permalink/latest",
+            ]
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use anyhow::{Context, Result};
+use reqwest::Response;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tracing::warn;
+
+/// Distinguishes temporary files of concurrent writers
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A cached response body with the validator needed to revalidate it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub url: String,
+    pub etag: Option<String>,
+    /// `Link` header, for paginated responses
+    #[serde(default)]
+    pub link: Option<String>,
+    /// Unix time the body was last fetched or confirmed unchanged
+    pub fetched_at: i64,
+    pub body: String,
+}
+
+impl CacheEntry {
+    pub fn new(url: &str, etag: Option<String>, link: Option<String>, body: String) -> Self {
+        Self {
+            url: url.to_string(),
+            etag,
+            link,
+            fetched_at: chrono::Utc::now().timestamp(),
+            body,
+        }
+    }
+
+    fn age(&self) -> Duration {
+        let age = chrono::Utc::now().timestamp() - self.fetched_at;
+        Duration::from_secs(age.max(0) as u64)
+    }
+}
+
+/// On-disk store of HTTP responses under `<root>/<sanitized url>.json`. Entries
+/// younger than the TTL are served without a request; older ones are revalidated
+/// with `If-None-Match`.
+pub struct HttpCache {
+    root: PathBuf,
+    ttl: Duration,
+}
+
+impl HttpCache {
+    pub fn new(root: PathBuf, ttl: Duration) -> Self {
+        Self { root, ttl }
+    }
+
+    /// The stored entry for a URL, fresh or not
+    pub fn load(&self, url: &str) -> Option<CacheEntry> {
+        let path = self.entry_path(url);
+        let data = fs::read(&path).ok()?;
+        match serde_json::from_slice::<CacheEntry>(&data) {
+            Ok(entry) if entry.url == url => Some(entry),
+            Ok(_) => None,
+            Err(e) => {
+                warn!("Ignoring unreadable HTTP cache entry {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Whether an entry can be served without revalidating it
+    pub fn is_fresh(&self, entry: &CacheEntry) -> bool {
+        entry.age() < self.ttl
+    }
+
+    pub fn store(&self, entry: &CacheEntry) -> Result<()> {
+        fs::create_dir_all(&self.root)?;
+        let path = self.entry_path(&entry.url);
+
+        // Write to a temporary file first so readers never see a truncated entry
+        let tmp = path.with_extension(format!(
+            "json.{}.tmp",
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&tmp, serde_json::to_vec(entry)?)
+            .with_context(|| format!("Failed to write {:?}", tmp))?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// Mark an entry as confirmed unchanged, restarting its TTL
+    pub fn touch(&self, entry: &mut CacheEntry) -> Result<()> {
+        entry.fetched_at = chrono::Utc::now().timestamp();
+        self.store(entry)
+    }
+
+    fn entry_path(&self, url: &str) -> PathBuf {
+        let name: String = url
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .collect();
+        self.root.join(format!("{}.json", name))
+    }
+}
+
+/// Body and `Link` header of a GET request, through the cache if there is one. Fresh
+/// entries are served without a request; otherwise `send` is called with the cached
+/// ETag, and a `304 Not Modified` restarts the entry's TTL. When `send` fails with an
+/// error `use_stale` accepts, such as a network error, the stale entry is served.
+pub async fn fetch<E, F, Fut>(
+    cache: Option<&HttpCache>,
+    url: &str,
+    send: F,
+    use_stale: impl FnOnce(&E) -> bool,
+) -> Result<(String, Option<String>), E>
+where
+    E: From<reqwest::Error> + Display,
+    F: FnOnce(Option<String>) -> Fut,
+    Fut: Future<Output = Result<Response, E>>,
+{
+    let Some(cache) = cache else {
+        let response = send(None).await?;
+        let link = header(&response, "link");
+        return Ok((response.text().await?, link));
+    };
+
+    let entry = cache.load(url);
+    if let Some(entry) = entry.as_ref().filter(|e| cache.is_fresh(e)) {
+        return Ok((entry.body.clone(), entry.link.clone()));
+    }
+
+    let etag = entry.as_ref().and_then(|e| e.etag.clone());
+    let response = match (send(etag).await, entry) {
+        (Ok(response), entry) => (response, entry),
+        (Err(e), Some(entry)) if use_stale(&e) => {
+            warn!("Using cached response for {}: {}", url, e);
+            return Ok((entry.body, entry.link));
+        }
+        (Err(e), _) => return Err(e),
+    };
+
+    match response {
+        (response, Some(mut entry)) if response.status() == StatusCode::NOT_MODIFIED => {
+            if let Err(e) = cache.touch(&mut entry) {
+                warn!("Failed to update HTTP cache for {}: {}", url, e);
+            }
+            Ok((entry.body, entry.link))
+        }
+        (response, _) => {
+            let etag = header(&response, "etag");
+            let link = header(&response, "link");
+            let body = response.text().await?;
+            let entry = CacheEntry::new(url, etag, link, body);
+            if let Err(e) = cache.store(&entry) {
+                warn!("Failed to update HTTP cache for {}: {}", url, e);
+            }
+            Ok((entry.body, entry.link))
+        }
+    }
+}
+
+/// Whether an error status is expected to pass, so a stale cached copy is better than
+/// failing: throttling and server errors. Shared by every forge client.
+pub fn is_transient(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+pub fn header(response: &Response, name: &str) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+}
+
+/// URL of the `rel="next"` page in a `Link` header
+pub fn next_page(link: &str) -> Option<String> {
+    link.split(',').find_map(|part| {
+        let (url, params) = part.split_once(';')?;
+        params
+            .split(';')
+            .any(|p| p.trim() == "rel=\"next\"")
+            .then(|| url.trim().trim_start_matches('<').trim_end_matches('>').to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_page_finds_the_next_link() {
+        let link = "<https://api.example/r?page=1>; rel=\"prev\", \
+                    <https://api.example/r?page=3>; rel=\"next\", \
+                    <https://api.example/r?page=9>; rel=\"last\"";
+        assert_eq!(next_page(link).as_deref(), Some("https://api.example/r?page=3"));
+    }
+
+    #[test]
+    fn next_page_is_none_on_the_last_page() {
+        let link = "<https://api.example/r?page=1>; rel=\"first\", \
+                    <https://api.example/r?page=8>; rel=\"prev\"";
+        assert_eq!(next_page(link), None);
+        assert_eq!(next_page(""), None);
+    }
+
+    #[test]
+    fn throttling_and_server_errors_are_transient() {
+        assert!(is_transient(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_transient(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_transient(StatusCode::NOT_FOUND));
+        assert!(!is_transient(StatusCode::FORBIDDEN));
+    }
+
+    #[test]
+    fn entries_are_fresh_until_the_ttl_passes() {
+        let cache = HttpCache::new(PathBuf::new(), Duration::from_secs(60));
+        let mut entry = CacheEntry::new("https://api.example/r", None, None, String::new());
+        assert!(cache.is_fresh(&entry));
+        entry.fetched_at -= 61;
+        assert!(!cache.is_fresh(&entry));
+        assert!(!HttpCache::new(PathBuf::new(), Duration::ZERO).is_fresh(&entry));
+    }
+
+    #[test]
+    fn entry_paths_are_sanitized() {
+        let cache = HttpCache::new(PathBuf::from("/cache"), Duration::ZERO);
+        assert_eq!(
+            cache.entry_path("https://api.example/repos/a/b?page=2"),
+            PathBuf::from("/cache/api_example_repos_a_b_page_2.json")
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use tracing::{info, warn};
+
+use crate::search::IndexedUnit;
+use crate::{Document, IdCollision};
+
+/// Bumped whenever the snapshot layout or the way documents are indexed changes
+const FORMAT_VERSION: u32 = 4;
+
+/// Snapshots kept per repository; older commits are indexed again if needed
+const MAX_SNAPSHOTS: usize = 20;
+
+/// Everything indexed for one repository at one commit, with file paths relative to
+/// the repository root
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RepoSnapshot {
+    format: u32,
+    rules: String,
+    pub documents: Vec<Document>,
+    pub units: Vec<IndexedUnit>,
+    pub collisions: Vec<IdCollision>,
+}
+
+impl RepoSnapshot {
+    pub fn new(
+        rules: String,
+        documents: Vec<Document>,
+        units: Vec<IndexedUnit>,
+        collisions: Vec<IdCollision>,
+    ) -> Self {
+        Self {
+            format: FORMAT_VERSION,
+            rules,
+            documents,
+            units,
+            collisions,
+        }
+    }
+}
+
+/// On-disk store of repository snapshots under `<root>/<repo>/<commit>/index.json`,
+/// keeping the most recently used snapshots of each repository
+#[derive(Clone)]
+pub struct IndexCache {
+    root: PathBuf,
+    max_snapshots: usize,
+}
+
+impl IndexCache {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            max_snapshots: MAX_SNAPSHOTS,
+        }
+    }
+
+    /// Load the snapshot for a commit, if one was stored with the same index rules
+    pub fn load(&self, repo: &str, commit: &str, rules: &str) -> Option<RepoSnapshot> {
+        let path = self.snapshot_path(repo, commit);
+        let data = fs::read(&path).ok()?;
+        match serde_json::from_slice::<RepoSnapshot>(&data) {
+            Ok(snapshot) if snapshot.format == FORMAT_VERSION && snapshot.rules == rules => {
+                // The modification time orders snapshots for eviction
+                if let Err(e) = fs::File::open(&path).and_then(|f| f.set_modified(SystemTime::now())) {
+                    warn!("Failed to mark {:?} as used: {}", path, e);
+                }
+                Some(snapshot)
+            }
+            Ok(_) => None,
+            Err(e) => {
+                warn!("Ignoring unreadable index cache {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    pub fn store(&self, repo: &str, commit: &str, snapshot: &RepoSnapshot) -> Result<()> {
+        let path = self.snapshot_path(repo, commit);
+        let dir = path.parent().unwrap();
+        fs::create_dir_all(dir)?;
+
+        // Write to a temporary file first so a crash never leaves a truncated snapshot
+        let tmp = dir.join("index.json.tmp");
+        fs::write(&tmp, serde_json::to_vec(snapshot)?)
+            .with_context(|| format!("Failed to write {:?}", tmp))?;
+        fs::rename(&tmp, &path)?;
+        self.evict(repo);
+        Ok(())
+    }
+
+    /// Remove the least recently used snapshots of a repository beyond `max_snapshots`
+    fn evict(&self, repo: &str) {
+        let Ok(entries) = fs::read_dir(self.root.join(repo)) else {
+            return;
+        };
+        let mut snapshots: Vec<(SystemTime, PathBuf)> = entries
+            .flatten()
+            .map(|entry| {
+                let used = fs::metadata(entry.path().join("index.json"))
+                    .and_then(|m| m.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                (used, entry.path())
+            })
+            .collect();
+        if snapshots.len() <= self.max_snapshots {
+            return;
+        }
+        snapshots.sort_by_key(|s| std::cmp::Reverse(s.0));
+        for (_, dir) in snapshots.drain(self.max_snapshots..) {
+            match fs::remove_dir_all(&dir) {
+                Ok(()) => info!("Removed index snapshot {:?}", dir),
+                Err(e) => warn!("Failed to remove index snapshot {:?}: {}", dir, e),
+            }
+        }
+    }
+
+    fn snapshot_path(&self, repo: &str, commit: &str) -> PathBuf {
+        self.root.join(repo).join(commit).join("index.json")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::SearchIndex;
+    use crate::test_support::TempDir;
+
+    fn snapshot(rules: &str) -> RepoSnapshot {
+        let mut index = SearchIndex::new();
+        index.insert("r/a#", "A", "some text");
+        let document = Document {
+            id: "r/a".to_string(),
+            title: "A".to_string(),
+            path: "a.md".to_string(),
+            content: "some text".to_string(),
+            category: "getting_started".to_string(),
+            repo: "r".to_string(),
+            version: "main".to_string(),
+            sections: crate::markdown::split_sections("some text"),
+        };
+        RepoSnapshot::new(
+            rules.to_string(),
+            vec![document],
+            vec![index.export("r/a#").unwrap()],
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn round_trips_snapshots() {
+        let dir = TempDir::new("index-cache-round-trip");
+        let cache = IndexCache::new(dir.path().to_path_buf());
+        cache.store("r", "c1", &snapshot("rules")).unwrap();
+
+        let loaded = cache.load("r", "c1", "rules").unwrap();
+        assert_eq!(loaded.documents.len(), 1);
+        assert_eq!(loaded.documents[0].path, "a.md");
+        assert_eq!(loaded.documents[0].content, "some text");
+        assert_eq!(loaded.units[0].id, "r/a#");
+
+        let mut index = SearchIndex::new();
+        index.import(loaded.units.into_iter().next().unwrap());
+        assert!(index.in_document("r/a#", "text"));
+    }
+
+    #[test]
+    fn rejects_snapshots_of_other_commits_rules_or_formats() {
+        let dir = TempDir::new("index-cache-reject");
+        let cache = IndexCache::new(dir.path().to_path_buf());
+        cache.store("r", "c1", &snapshot("rules")).unwrap();
+
+        assert!(cache.load("r", "c2", "rules").is_none());
+        assert!(cache.load("other", "c1", "rules").is_none());
+        assert!(cache.load("r", "c1", "other rules").is_none());
+
+        // Snapshots written by other versions of the server are ignored
+        let path = cache.snapshot_path("r", "c1");
+        let mut json: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        json["format"] = (FORMAT_VERSION - 1).into();
+        fs::write(&path, json.to_string()).unwrap();
+        assert!(cache.load("r", "c1", "rules").is_none());
+
+        fs::write(&path, "{ truncated").unwrap();
+        assert!(cache.load("r", "c1", "rules").is_none());
+    }
+
+    #[test]
+    fn evicts_least_recently_used_snapshots() {
+        let dir = TempDir::new("index-cache-evict");
+        let mut cache = IndexCache::new(dir.path().to_path_buf());
+        cache.max_snapshots = 2;
+        let age = |commit: &str, secs: u64| {
+            let used = SystemTime::now() - std::time::Duration::from_secs(secs);
+            let file = fs::File::open(cache.snapshot_path("r", commit)).unwrap();
+            file.set_modified(used).unwrap();
+        };
+        cache.store("r", "c1", &snapshot("rules")).unwrap();
+        cache.store("r", "c2", &snapshot("rules")).unwrap();
+        age("c1", 300);
+        age("c2", 200);
+
+        // Loading c1 makes c2 the least recently used
+        assert!(cache.load("r", "c1", "rules").is_some());
+        cache.store("r", "c3", &snapshot("rules")).unwrap();
+        assert!(cache.load("r", "c1", "rules").is_some());
+        assert!(cache.load("r", "c2", "rules").is_none());
+        assert!(cache.load("r", "c3", "rules").is_some());
+
+        // Other repositories have their own snapshots
+        cache.store("other", "c1", &snapshot("rules")).unwrap();
+        assert!(cache.load("r", "c1", "rules").is_some());
+        assert!(cache.load("r", "c3", "rules").is_some());
+    }
+}
@@ -1,21 +1,48 @@
+mod changelog;
+mod config;
+mod forge;
+mod git_forge;
+mod gitea;
 mod github;
+mod gitlab;
+mod http_cache;
+mod index_cache;
+mod markdown;
+mod query;
+mod refresh;
 mod repo_manager;
+mod rules;
+mod search;
+mod snippet;
+#[cfg(test)]
+mod test_support;
+mod transport;
+mod versions;
+mod watcher;
 
 use anyhow::Result;
 use jsonrpc_core::{IoHandler, Params, Value};
-use jsonrpc_stdio_server::ServerBuilder;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use std::sync::{Arc, Mutex};
-use tracing::info;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
 use walkdir::WalkDir;
 
-use repo_manager::RepoManager;
+use config::{Config, RepoConfig};
+use index_cache::{IndexCache, RepoSnapshot};
+use markdown::Section;
+use query::{Query, Target};
+use repo_manager::{RepoManager, VersionFilter, WorktreeLease};
+use rules::IndexRules;
+use search::SearchIndex;
+use snippet::SnippetOptions;
 
 // Include SERVER_INFO.md at compile time
 const SERVER_INFO: &str = include_str!("../SERVER_INFO.md");
@@ -28,128 +55,696 @@ struct Document {
     content: String,
     category: String,
     repo: String,
+    /// Version of the repository the document was indexed from
+    #[serde(default)]
+    version: String,
+    sections: Vec<Section>,
 }
 
+impl Document {
+    /// Key the document is stored under: its id qualified by its version
+    fn key(&self) -> String {
+        document_key(&self.version, &self.id)
+    }
+    
+    /// Resource URI for a section, or for the whole document when the anchor is empty.
+    /// Unqualified URIs follow the repository's active version.
+    fn uri(&self, anchor: &str) -> String {
+        with_anchor(format!("dynamo://docs/{}", self.id), anchor)
+    }
+    
+    /// Resource URI pinned to the document's version, e.g. `dynamo://docs/v0.4.0/dynamo/README`
+    fn versioned_uri(&self, anchor: &str) -> String {
+        with_anchor(format!("dynamo://docs/{}", self.key()), anchor)
+    }
+    
+    fn section(&self, anchor: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.anchor == anchor)
+    }
+    
+    fn section_text(&self, section: &Section) -> &str {
+        &self.content[section.start..section.end]
+    }
+    
+    /// Title a section is indexed under: its own heading. The document title is added to
+    /// the section it heads (the first top-level one, or the first section), so title
+    /// matches are boosted once per document rather than in every section.
+    fn section_title(&self, section: &Section) -> String {
+        let heading = section.heading_path.last().map_or("", |h| h.as_str());
+        let titled = self.sections
+            .iter()
+            .find(|s| s.level == 1)
+            .or(self.sections.first());
+        if titled.is_some_and(|s| s.start == section.start) && heading != self.title {
+            format!("{} {}", self.title, heading).trim_end().to_string()
+        } else {
+            heading.to_string()
+        }
+    }
+}
+
+fn with_anchor(uri: String, anchor: &str) -> String {
+    if anchor.is_empty() {
+        uri
+    } else {
+        format!("{}#{}", uri, anchor)
+    }
+}
+
+/// Two files whose document ids differ only by letter case, such as `docs/README.md`
+/// and `docs/readme.md`. Only one of them survives a checkout on a case-insensitive
+/// filesystem, so the first one indexed is kept and the other is reported.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IdCollision {
+    /// Id of the kept document
+    id: String,
+    #[serde(default)]
+    version: String,
+    kept: String,
+    skipped: String,
+}
+
+/// Path of a file relative to the repository root, with `/` separators
+fn relative_path(base_path: &Path, path: &Path) -> String {
+    path.strip_prefix(base_path)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Stable document id: the repository name followed by the file's path relative to
+/// the repository root, without the `.md` extension (e.g. `dynamo/docs/guides/a/README`)
+fn document_id(repo_name: &str, relative: &str) -> String {
+    let relative = relative.strip_suffix(".md").unwrap_or(relative);
+    format!("{}/{}", repo_name, relative)
+}
+
+/// Version as a single URI path segment: branch names may contain `/`
+fn version_segment(version: &str) -> String {
+    version.replace('%', "%25").replace('/', "%2F")
+}
+
+fn parse_version_segment(segment: &str) -> String {
+    segment.replace("%2F", "/").replace("%2f", "/").replace("%25", "%")
+}
+
+/// Split what follows `dynamo://docs/` into the version it is pinned to, the document
+/// id and the anchor. `dynamo://docs/{id}` follows the active version and
+/// `dynamo://docs/{version}/{id}` is pinned to one; ids always start with a repository
+/// name, which `is_repo` recognizes.
+fn parse_doc_uri(path: &str, is_repo: impl Fn(&str) -> bool) -> (Option<String>, &str, &str) {
+    let (path, anchor) = path.split_once('#').unwrap_or((path, ""));
+    match path.split_once('/') {
+        Some((version, doc_id)) if !is_repo(version) => (Some(parse_version_segment(version)), doc_id, anchor),
+        _ => (None, path, anchor),
+    }
+}
+
+/// Document id qualified by a version, e.g. `v0.4.0/dynamo/docs/guides/README`
+fn document_key(version: &str, id: &str) -> String {
+    format!("{}/{}", version_segment(version), id)
+}
+
+/// Title for a file without a top-level heading: the directory name for READMEs,
+/// otherwise the file stem with underscores as spaces
+fn fallback_title(path: &Path) -> String {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    if stem.eq_ignore_ascii_case("readme") {
+        if let Some(dir) = path.parent().and_then(|p| p.file_name()) {
+            return dir.to_string_lossy().to_string();
+        }
+    }
+    stem.replace('_', " ")
+}
+
+/// Key a section is stored under in the search index
+fn section_key(doc_key: &str, anchor: &str) -> String {
+    format!("{}#{}", doc_key, anchor)
+}
+
+/// How many versions of a repository stay indexed, including the active one
+const MAX_LOADED_VERSIONS: usize = 5;
+
+/// Documents of every loaded repository version. Each repository has an active
+/// version, which is what unqualified ids, URIs and searches refer to; other versions
+/// are loaded on demand and evicted least recently used first.
 struct DocumentIndex {
+    /// Keyed by `Document::key`
     documents: HashMap<String, Document>,
+    search_index: SearchIndex,
+    /// Keys by their lowercase form, to find ids that differ only by case
+    folded_keys: HashMap<String, String>,
+    collisions: Vec<IdCollision>,
+    /// Active version per repository
+    active: HashMap<String, String>,
+    /// Loaded `(repo, version)` pairs, least recently used first
+    loaded: Vec<(String, String)>,
+    /// Keep the worktrees of loaded versions from being cleaned up
+    leases: HashMap<(String, String), WorktreeLease>,
+    /// Which files to index, per repository
+    rules: HashMap<String, IndexRules>,
+    cache: IndexCache,
 }
 
 impl DocumentIndex {
-    fn new() -> Self {
+    fn new(rules: HashMap<String, IndexRules>, cache: IndexCache) -> Self {
         Self {
             documents: HashMap::new(),
+            search_index: SearchIndex::new(),
+            folded_keys: HashMap::new(),
+            collisions: Vec::new(),
+            active: HashMap::new(),
+            loaded: Vec::new(),
+            leases: HashMap::new(),
+            rules,
+            cache,
         }
     }
-
-    fn index_from_manager(&mut self, repo_manager: &RepoManager) -> Result<()> {
-        // Index dotfiles if available
-        if let Some(dotfiles_path) = repo_manager.get_path("dynamo-dotfiles") {
-            self.index_dotfiles(&dotfiles_path)?;
-            info!("Indexed dotfiles from {:?}", dotfiles_path);
-        }
-        
-        // Index dynamo if available
-        if let Some(dynamo_path) = repo_manager.get_path("dynamo") {
-            self.index_dynamo(&dynamo_path)?;
-            info!("Indexed dynamo from {:?}", dynamo_path);
+    
+    async fn index_from_manager(&mut self, repo_manager: &RepoManager) -> Result<()> {
+        let mut names: Vec<String> = self.rules.keys().cloned().collect();
+        names.sort();
+        for name in &names {
+            if let (Some(path), Some(version)) = (repo_manager.get_path(name), repo_manager.current_version(name)) {
+                self.load_repo(name, &version, &path, repo_manager.worktree_commit(name).await.as_deref())?;
+                self.activate(name, &version);
+            }
         }
         
         Ok(())
     }
     
-    fn index_dotfiles(&mut self, base_path: &Path) -> Result<()> {
-        let readme_path = base_path.join("README.md");
-        if readme_path.exists() {
-            self.add_document(
-                "dotfiles-readme",
-                "Dynamo Dotfiles Overview",
-                readme_path,
-                "getting_started",
-                "dynamo_dotfiles",
-            )?;
+    /// Start indexing a repository with the given rules
+    fn add_rules(&mut self, name: &str, rules: IndexRules) {
+        self.rules.insert(name.to_string(), rules);
+    }
+    
+    /// Stop indexing a repository and drop its documents
+    fn forget_repo(&mut self, name: &str) {
+        self.remove_repo(name);
+        self.rules.remove(name);
+    }
+    
+    /// Make a loaded version the one unqualified ids and searches refer to
+    fn activate(&mut self, name: &str, version: &str) {
+        self.active.insert(name.to_string(), version.to_string());
+        self.touch(name, version);
+    }
+    
+    fn active_version(&self, name: &str) -> Option<&str> {
+        self.active.get(name).map(|v| v.as_str())
+    }
+    
+    fn is_loaded(&self, name: &str, version: &str) -> bool {
+        self.loaded.iter().any(|(n, v)| n == name && v == version)
+    }
+    
+    /// Mark a version as recently used, evicting the least recently used inactive
+    /// versions of the repository beyond `MAX_LOADED_VERSIONS`
+    fn touch(&mut self, name: &str, version: &str) {
+        self.loaded.retain(|(n, v)| !(n == name && v == version));
+        self.loaded.push((name.to_string(), version.to_string()));
+        
+        let active = self.active.get(name).cloned();
+        let mut inactive: Vec<String> = self.loaded
+            .iter()
+            .filter(|(n, v)| n == name && Some(v) != active.as_ref())
+            .map(|(_, v)| v.clone())
+            .collect();
+        let loaded = inactive.len() + usize::from(active.is_some());
+        if loaded > MAX_LOADED_VERSIONS {
+            for version in inactive.drain(..loaded - MAX_LOADED_VERSIONS) {
+                info!("Unloading {} at {}", name, version);
+                self.remove_version(name, &version);
+            }
         }
+    }
+    
+    /// Replace the documents of a repository version with those at `path`, see
+    /// `RepoLoader::build`
+    fn load_repo(&mut self, name: &str, version: &str, path: &Path, commit: Option<&str>) -> Result<()> {
+        let Some(loader) = self.loader(name) else {
+            return Ok(());
+        };
+        let snapshot = loader.build(version, path, commit)?;
+        self.install(name, version, path, snapshot);
         Ok(())
     }
     
-    fn index_dynamo(&mut self, base_path: &Path) -> Result<()> {
-        let docs_path = base_path.join("docs");
+    /// What it takes to index a repository apart from this index, None if it isn't indexed
+    fn loader(&self, name: &str) -> Option<RepoLoader> {
+        Some(RepoLoader {
+            name: name.to_string(),
+            rules: self.rules.get(name)?.clone(),
+            cache: self.cache.clone(),
+        })
+    }
+    
+    /// Replace the documents of a repository version with a snapshot built from the
+    /// checkout at `base_path`
+    fn install(&mut self, name: &str, version: &str, base_path: &Path, snapshot: RepoSnapshot) {
+        self.remove_version(name, version);
+        self.touch(name, version);
+        self.leases.insert((name.to_string(), version.to_string()), WorktreeLease::hold(base_path));
+        self.restore(snapshot, version, base_path);
+    }
+    
+    /// Drop all documents belonging to a repository, at every version
+    fn remove_repo(&mut self, name: &str) {
+        let versions: Vec<String> = self.loaded
+            .iter()
+            .filter(|(n, _)| n == name)
+            .map(|(_, v)| v.clone())
+            .collect();
+        for version in versions {
+            self.remove_version(name, &version);
+        }
+        self.active.remove(name);
+    }
+    
+    /// Drop the documents of one version of a repository
+    fn remove_version(&mut self, name: &str, version: &str) {
+        let keys: Vec<String> = self.documents
+            .values()
+            .filter(|doc| doc.repo == name && doc.version == version)
+            .map(|doc| doc.key())
+            .collect();
+        for key in keys {
+            self.remove_document(&key);
+        }
+        let prefix = format!("{}/", name);
+        self.collisions.retain(|c| !(c.id.starts_with(&prefix) && c.version == version));
+        self.loaded.retain(|(n, v)| !(n == name && v == version));
+        self.leases.remove(&(name.to_string(), version.to_string()));
+    }
+    
+    /// Drop a document and its sections from the index. Returns whether it existed.
+    fn remove_document(&mut self, key: &str) -> bool {
+        let Some(doc) = self.documents.remove(key) else {
+            return false;
+        };
+        self.folded_keys.remove(&key.to_lowercase());
+        for section in &doc.sections {
+            self.search_index.remove(&section_key(key, &section.anchor));
+        }
+        true
+    }
+    
+    /// Document by id, at the given version or the repository's active one
+    fn get(&self, id: &str, version: Option<&str>) -> Option<&Document> {
+        let repo = id.split('/').next()?;
+        let version = version.or_else(|| self.active_version(repo))?;
+        self.documents.get(&document_key(version, id))
+    }
+    
+    /// Documents at each repository's active version
+    fn active_documents(&self) -> impl Iterator<Item = &Document> {
+        self.documents
+            .values()
+            .filter(|doc| self.active_version(&doc.repo) == Some(doc.version.as_str()))
+    }
+    
+    fn document_count(&self, name: &str, version: &str) -> usize {
+        self.documents
+            .values()
+            .filter(|doc| doc.repo == name && doc.version == version)
+            .count()
+    }
+    
+    /// Bring the active version of a repository up to date with a created, modified
+    /// or deleted file in its checkout. Returns whether any document changed.
+    fn update_path(&mut self, name: &str, base_path: &Path, path: &Path) -> Result<bool> {
+        let (Some(rules), Some(version)) = (self.rules.get(name).cloned(), self.active.get(name).cloned()) else {
+            return Ok(false);
+        };
+        let relative = relative_path(base_path, path);
+        if relative.split('/').any(|c| c.starts_with('.')) || path.is_dir() {
+            return Ok(false);
+        }
         
-        // Index architecture docs
-        let arch_path = docs_path.join("architecture");
-        if arch_path.exists() {
-            for entry in WalkDir::new(&arch_path).max_depth(2) {
-                let entry = entry?;
-                if entry.path().extension().and_then(|s| s.to_str()) == Some("md") {
-                    let doc_id = format!("arch-{}", entry.file_name().to_string_lossy().replace(".md", ""));
-                    let title = entry.file_name().to_string_lossy().replace('_', " ");
-                    self.add_document(
-                        &doc_id,
-                        &title,
-                        entry.path().to_path_buf(),
-                        "architecture",
-                        "dynamo",
-                    )?;
-                }
+        let id = document_id(name, &relative);
+        let key = document_key(&version, &id);
+        let is_markdown = path.extension().and_then(|s| s.to_str()) == Some("md");
+        
+        if !is_markdown {
+            // A removed directory takes every document below it with it
+            if path.exists() {
+                return Ok(false);
             }
+            let prefix = format!("{}/", key);
+            let keys: Vec<String> = self.documents
+                .keys()
+                .filter(|doc_key| doc_key.starts_with(&prefix))
+                .cloned()
+                .collect();
+            for doc_key in &keys {
+                self.remove_document(doc_key);
+            }
+            return Ok(!keys.is_empty());
+        }
+        
+        let removed = self.remove_document(&key);
+        if path.is_file() && rules.matches(&relative) {
+            let added = self.add_document(&id, path.to_path_buf(), &rules.category_for(&relative), name, &version)?;
+            return Ok(added || removed);
+        }
+        Ok(removed)
+    }
+    
+    /// Documents of a repository version with paths relative to `base_path`, so the
+    /// snapshot can be restored from another checkout of the same commit
+    fn snapshot(&self, name: &str, version: &str, base_path: &Path, rules: &IndexRules) -> RepoSnapshot {
+        let documents: Vec<Document> = self.documents
+            .values()
+            .filter(|doc| doc.repo == name && doc.version == version)
+            .map(|doc| Document {
+                path: relative_path(base_path, Path::new(&doc.path)),
+                ..doc.clone()
+            })
+            .collect();
+        // Units are stored under unversioned keys, so the snapshot can be restored as
+        // any version at the same commit
+        let units = documents
+            .iter()
+            .flat_map(|doc| doc.sections.iter().filter_map(move |s| {
+                let mut unit = self.search_index.export(&section_key(&doc.key(), &s.anchor))?;
+                unit.id = section_key(&doc.id, &s.anchor);
+                Some(unit)
+            }))
+            .collect();
+        let prefix = format!("{}/", name);
+        let collisions = self.collisions
+            .iter()
+            .filter(|c| c.id.starts_with(&prefix) && c.version == version)
+            .map(|c| IdCollision {
+                kept: relative_path(base_path, Path::new(&c.kept)),
+                skipped: relative_path(base_path, Path::new(&c.skipped)),
+                ..c.clone()
+            })
+            .collect();
+        RepoSnapshot::new(rules.fingerprint(), documents, units, collisions)
+    }
+    
+    /// Load a snapshot as the given version, checked out at `base_path`. Snapshots are
+    /// keyed by commit, so the version and checkout they were stored from may differ
+    /// (e.g. two tags on one commit).
+    fn restore(&mut self, snapshot: RepoSnapshot, version: &str, base_path: &Path) {
+        let rebase = |relative: &str| base_path.join(relative).to_string_lossy().to_string();
+        for mut unit in snapshot.units {
+            unit.id = document_key(version, &unit.id);
+            self.search_index.import(unit);
         }
+        for mut doc in snapshot.documents {
+            doc.version = version.to_string();
+            doc.path = rebase(&doc.path);
+            self.folded_keys.insert(doc.key().to_lowercase(), doc.key());
+            self.documents.insert(doc.key(), doc);
+        }
+        for mut collision in snapshot.collisions {
+            collision.version = version.to_string();
+            collision.kept = rebase(&collision.kept);
+            collision.skipped = rebase(&collision.skipped);
+            self.collisions.push(collision);
+        }
+    }
+    
+    /// Index every markdown file in the repository selected by its rules. Files that
+    /// can't be read, or aren't UTF-8, are logged and skipped.
+    fn index_repo(&mut self, name: &str, version: &str, base_path: &Path, rules: &IndexRules) -> Result<()> {
+        // Don't descend into hidden or excluded directories such as `target/` at all
+        let walker = WalkDir::new(base_path)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| {
+                e.depth() == 0 || !(e.file_name().to_string_lossy().starts_with('.')
+                    || e.file_type().is_dir() && rules.excludes_dir(&relative_path(base_path, e.path())))
+            });
         
-        // Index guides
-        let guides_path = docs_path.join("guides");
-        if guides_path.exists() {
-            for entry in WalkDir::new(&guides_path).max_depth(3) {
-                let entry = entry?;
-                if entry.path().extension().and_then(|s| s.to_str()) == Some("md") {
-                    let doc_id = format!("guide-{}", entry.file_name().to_string_lossy().replace(".md", ""));
-                    let title = entry.file_name().to_string_lossy().replace('_', " ");
-                    self.add_document(
-                        &doc_id,
-                        &title,
-                        entry.path().to_path_buf(),
-                        "guide",
-                        "dynamo",
-                    )?;
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn!("Skipping unreadable entry in {:?}: {}", base_path, e);
+                    continue;
                 }
+            };
+            if !entry.file_type().is_file()
+                || entry.path().extension().and_then(|s| s.to_str()) != Some("md")
+            {
+                continue;
+            }
+            
+            let relative = relative_path(base_path, entry.path());
+            if !rules.matches(&relative) {
+                continue;
+            }
+            
+            let added = self.add_document(
+                &document_id(name, &relative),
+                entry.path().to_path_buf(),
+                &rules.category_for(&relative),
+                name,
+                version,
+            );
+            if let Err(e) = added {
+                warn!("Skipping {:?}: {}", entry.path(), e);
             }
         }
         
         Ok(())
     }
     
+    /// Index a file as a document. Returns false when its id collides with another
+    /// document's, see `IdCollision`.
     fn add_document(
         &mut self,
         id: &str,
-        title: &str,
         path: PathBuf,
         category: &str,
         repo: &str,
-    ) -> Result<()> {
+        version: &str,
+    ) -> Result<bool> {
+        let key = document_key(version, id);
+        let existing = self.folded_keys
+            .get(&key.to_lowercase())
+            .and_then(|existing| self.documents.get(existing));
+        if let Some(existing) = existing {
+            let collision = IdCollision {
+                id: existing.id.clone(),
+                version: version.to_string(),
+                kept: existing.path.clone(),
+                skipped: path.to_string_lossy().to_string(),
+            };
+            // Re-indexing a changed file reports it again
+            if !self.collisions.iter().any(|c| c.version == collision.version && c.skipped == collision.skipped) {
+                warn!("Document id collision for {}: keeping {}, skipping {}",
+                    collision.id, collision.kept, collision.skipped);
+                self.collisions.push(collision);
+            }
+            return Ok(false);
+        }
+        
         let content = fs::read_to_string(&path)?;
+        let sections = markdown::split_sections(&content);
         let doc = Document {
             id: id.to_string(),
-            title: title.to_string(),
+            title: markdown::title(&content).unwrap_or_else(|| fallback_title(&path)),
             path: path.to_string_lossy().to_string(),
             content,
             category: category.to_string(),
             repo: repo.to_string(),
+            version: version.to_string(),
+            sections,
         };
-        self.documents.insert(id.to_string(), doc);
-        Ok(())
+        for section in &doc.sections {
+            self.search_index.insert(
+                &section_key(&key, &section.anchor),
+                &doc.section_title(section),
+                doc.section_text(section),
+            );
+        }
+        self.folded_keys.insert(key.to_lowercase(), key.clone());
+        self.documents.insert(key, doc);
+        Ok(true)
     }
     
-    fn search(&self, query: &str) -> Vec<Document> {
-        let query_lower = query.to_lowercase();
-        self.documents
-            .values()
-            .filter(|doc| {
-                doc.title.to_lowercase().contains(&query_lower) ||
-                doc.content.to_lowercase().contains(&query_lower)
-            })
-            .cloned()
-            .collect()
+    /// Search document sections matching the query, ranked by BM25 score (highest first).
+    /// Only the given version of each repository is searched; repositories without an
+    /// entry in `versions` are skipped.
+    ///
+    /// When no single section of a document matches, e.g. because the terms of an
+    /// implicit AND are spread over several sections, the query is evaluated against
+    /// the whole document and its sections with any of the terms are returned.
+    /// Exclusions always apply to the whole document.
+    fn search(&self, query: &Query, versions: &HashMap<String, String>) -> Vec<(&Document, &Section, f32)> {
+        let scores = self.search_index.score(&query.scoring_terms());
+        
+        // Only documents with every term some group requires can match
+        let candidates: Vec<&Document> = match query.required_terms() {
+            Some(groups) => {
+                let mut keys: HashSet<&str> = HashSet::new();
+                for terms in groups {
+                    let mut docs: Option<HashSet<&str>> = None;
+                    for term in terms {
+                        let with_term: HashSet<&str> = self.search_index
+                            .units_with(term)
+                            .map(|key| key.rsplit_once('#').map_or(key, |(doc_key, _)| doc_key))
+                            .collect();
+                        docs = Some(match docs {
+                            Some(docs) => docs.intersection(&with_term).copied().collect(),
+                            None => with_term,
+                        });
+                    }
+                    keys.extend(docs.unwrap_or_default());
+                }
+                keys.into_iter().filter_map(|key| self.documents.get(key)).collect()
+            },
+            None => self.documents.values().collect(),
+        };
+        
+        let mut results = Vec::new();
+        let documents = candidates
+            .into_iter()
+            .filter(|doc| versions.get(&doc.repo) == Some(&doc.version));
+        for doc in documents {
+            let doc_key = doc.key();
+            let keys: Vec<String> = doc.sections
+                .iter()
+                .map(|section| section_key(&doc_key, &section.anchor))
+                .collect();
+            let document = Target::new(&keys, &doc.title, &doc.content, &doc.category, &doc.repo);
+            let matched = results.len();
+            for (section, key) in doc.sections.iter().zip(&keys) {
+                let title = doc.section_title(section);
+                let target = Target::new(
+                    std::slice::from_ref(key),
+                    &title,
+                    doc.section_text(section),
+                    &doc.category,
+                    &doc.repo,
+                );
+                if query.matches_within(&target, &document, &self.search_index) {
+                    let score = scores.get(key).copied().unwrap_or(0.0);
+                    results.push((doc, section, score));
+                }
+            }
+            
+            if results.len() > matched {
+                continue;
+            }
+            if query.matches(&document, &self.search_index) {
+                for (section, key) in doc.sections.iter().zip(&keys) {
+                    if let Some(score) = scores.get(key) {
+                        results.push((doc, section, *score));
+                    }
+                }
+            }
+        }
+        results.sort_by(|a, b| {
+            b.2.total_cmp(&a.2)
+                .then_with(|| a.0.id.cmp(&b.0.id))
+                .then_with(|| a.1.start_line.cmp(&b.1.start_line))
+        });
+        results
     }
 }
 
-fn main() -> Result<()> {
+/// Indexes one repository on its own, so that the slow part of loading a version can run
+/// without holding the `DocumentIndex` lock
+struct RepoLoader {
+    name: String,
+    rules: IndexRules,
+    cache: IndexCache,
+}
+
+impl RepoLoader {
+    /// Index the documents at `path`. When the worktree is at a known commit, a snapshot
+    /// persisted for that commit is used instead of re-indexing, and freshly built
+    /// indexes are persisted for next time.
+    fn build(&self, version: &str, path: &Path, commit: Option<&str>) -> Result<RepoSnapshot> {
+        let name = &self.name;
+        if let Some(commit) = commit {
+            if let Some(snapshot) = self.cache.load(name, commit, &self.rules.fingerprint()) {
+                info!("Loaded {} documents for {} at {} from index cache",
+                    snapshot.documents.len(), name, commit);
+                return Ok(snapshot);
+            }
+        }
+        
+        let mut scratch = DocumentIndex::new(HashMap::new(), self.cache.clone());
+        scratch.add_rules(name, self.rules.clone());
+        scratch.index_repo(name, version, path, &self.rules)?;
+        info!("Indexed {} at {} from {:?}", name, version, path);
+        
+        let snapshot = scratch.snapshot(name, version, path, &self.rules);
+        if let Some(commit) = commit {
+            self.persist(commit, &snapshot);
+        }
+        Ok(snapshot)
+    }
+    
+    /// Persist a snapshot of the repository for `commit`, logging failures
+    fn persist(&self, commit: &str, snapshot: &RepoSnapshot) {
+        if let Err(e) = self.cache.store(&self.name, commit, snapshot) {
+            warn!("Failed to persist index for {} at {}: {}", self.name, commit, e);
+        }
+    }
+}
+
+/// Index a repository version on a blocking thread, without holding the index lock.
+/// None if the repository isn't indexed.
+async fn build_version(
+    index: &RwLock<DocumentIndex>,
+    name: &str,
+    version: &str,
+    path: &Path,
+    commit: Option<&str>,
+) -> Result<Option<RepoSnapshot>> {
+    let Some(loader) = index.read().await.loader(name) else {
+        return Ok(None);
+    };
+    let (version, path, commit) = (version.to_string(), path.to_path_buf(), commit.map(str::to_string));
+    let snapshot = tokio::task::spawn_blocking(move || loader.build(&version, &path, commit.as_deref())).await??;
+    Ok(Some(snapshot))
+}
+
+/// Make sure a version of a repository is indexed, checking it out if needed, without
+/// changing the active version. Returns the version it resolved to.
+async fn load_version(
+    index: &RwLock<DocumentIndex>,
+    repo_manager: &RwLock<RepoManager>,
+    name: &str,
+    version: &str,
+) -> Result<String> {
+    // Aliases such as `latest` are never loaded under their own name
+    let resolved = repo_manager.read().await.resolved_version(name, version);
+    let version = resolved.as_deref().unwrap_or(version);
+    {
+        let mut index = index.write().await;
+        if index.is_loaded(name, version) {
+            index.touch(name, version);
+            return Ok(version.to_string());
+        }
+    }
+    
+    // Check out without holding the index, so other requests aren't held up
+    let checkout = repo_manager.read().await.checkout_version(name, version).await?;
+    if index.read().await.is_loaded(name, &checkout.version) {
+        index.write().await.touch(name, &checkout.version);
+        return Ok(checkout.version);
+    }
+    let snapshot = build_version(index, name, &checkout.version, &checkout.path, checkout.commit.as_deref()).await?;
+    let mut index = index.write().await;
+    if let Some(snapshot) = snapshot {
+        index.install(name, &checkout.version, &checkout.path, snapshot);
+    }
+    Ok(checkout.version)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
     // Initialize logging
     tracing_subscriber::fmt()
         .with_env_filter("dynamo_mcp=info")
@@ -158,61 +753,114 @@ fn main() -> Result<()> {
     
     info!("Starting Dynamo MCP Server (Rust)");
     
-    // Setup repository manager
-    let mut repo_manager = RepoManager::new()?;
+    // Load the repository registry
+    let config = Config::load()?;
     
     // Check environment variables for configuration
     let use_local = env::var("DYNAMO_USE_LOCAL").is_ok();
-    let dynamo_version = env::var("DYNAMO_VERSION").ok();
-    let dotfiles_version = env::var("DYNAMO_DOTFILES_VERSION").ok();
+    let offline = env::var("DYNAMO_OFFLINE").is_ok();
+    
+    // Setup repository manager
+    let mut repo_manager = RepoManager::new(offline)?;
+    if offline {
+        info!("Offline mode: using cached repositories only");
+    }
     
     // Setup repositories
-    info!("Setting up repositories...");
-    
-    // Setup dynamo repository
-    repo_manager.setup_repo(
-        "dynamo",
-        "ai-dynamo",
-        "dynamo",
-        dynamo_version.as_deref(),
-        use_local,
-    )?;
-    
-    // Setup dynamo-dotfiles repository
-    repo_manager.setup_repo(
-        "dynamo-dotfiles",
-        "ryanolson",
-        "dynamo-dotfiles",
-        dotfiles_version.as_deref(),
-        use_local,
-    )?;
+    info!("Setting up repositories: {}", config.names().join(", "));
+    let mut rules = HashMap::new();
+    for repo in &config.repos {
+        let set_up = repo_manager.setup_repo(
+            &repo.name,
+            &repo.owner,
+            repo.repo_name(),
+            &repo.forge_config(),
+            repo.version().as_deref(),
+            use_local,
+        ).await;
+        match set_up {
+            Ok(_) => {},
+            // Offline, a repository that was never cloned can't be served, but the rest can
+            Err(e) if offline => {
+                warn!("Skipping {}: {}", repo.name, e);
+                continue;
+            },
+            Err(e) => return Err(e),
+        }
+        rules.insert(repo.name.clone(), repo.index_rules()?);
+    }
+    
+    // Tell clients up front that offline data may be out of date
+    let instructions = if offline {
+        let mut notice = String::from("**Offline mode:** the network is never used. Repositories, versions and releases come from the local cache and may be stale; GitHub releases are not listed and refresh_repos is disabled.\n");
+        for repo in &config.repos {
+            let fetched = repo_manager.last_fetched(&repo.name)
+                .map(|time| format!("last fetched {}", time.format("%Y-%m-%d %H:%M UTC")))
+                .unwrap_or_else(|| "not available".to_string());
+            notice.push_str(&format!("- {}: {}\n", repo.name, fetched));
+        }
+        format!("{}\n{}", notice, SERVER_INFO)
+    } else {
+        SERVER_INFO.to_string()
+    };
     
     // Index documents
-    let mut index = DocumentIndex::new();
-    index.index_from_manager(&repo_manager)?;
+    let mut index = DocumentIndex::new(rules, IndexCache::new(repo_manager.index_cache_dir()));
+    index.index_from_manager(&repo_manager).await?;
     info!("Indexed {} documents", index.documents.len());
+    if !index.collisions.is_empty() {
+        warn!("{} files skipped due to document id collisions", index.collisions.len());
+    }
     
     // Create JSON-RPC handler
     let mut io = IoHandler::new();
     
-    // Wrap index and repo_manager in Arc<Mutex> for thread-safe sharing
-    let index_clone = Arc::new(Mutex::new(index));
-    let repo_manager_clone = Arc::new(Mutex::new(repo_manager));
+    // Wrap index and repo_manager in Arc<RwLock> for sharing between requests. Slow git
+    // and network operations only take read locks, so they don't hold up searches.
+    let local_repos = repo_manager.local_repos();
+    let index_clone = Arc::new(RwLock::new(index));
+    let repo_manager_clone = Arc::new(RwLock::new(repo_manager));
+    
+    // Re-index local checkouts as their docs are edited
+    let _watcher = match watcher::watch_local_repos(local_repos, index_clone.clone(), repo_manager_clone.clone()) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            warn!("Failed to watch local repositories: {}", e);
+            None
+        }
+    };
+    
+    // Keep repositories that track a branch up to date in the background
+    let refresh_interval = env::var("DYNAMO_REFRESH_INTERVAL")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&seconds: &u64| seconds > 0);
+    if let Some(seconds) = refresh_interval {
+        if offline {
+            warn!("Ignoring DYNAMO_REFRESH_INTERVAL in offline mode");
+        } else {
+            info!("Refreshing repositories every {}s", seconds);
+            refresh::spawn_periodic(Duration::from_secs(seconds), index_clone.clone(), repo_manager_clone.clone());
+        }
+    }
     
     // Handle initialize
-    io.add_method("initialize", |_params: Params| async {
-        Ok(json!({
-            "protocolVersion": "2024-11-05",
-            "capabilities": {
-                "resources": {"listChanged": false},
-                "tools": {},
-            },
-            "serverInfo": {
-                "name": "dynamo-mcp-rust",
-                "version": "0.1.0",
-                "instructions": SERVER_INFO
-            }
-        }))
+    let initialize_result = json!({
+        "protocolVersion": "2024-11-05",
+        "capabilities": {
+            "resources": {"listChanged": true},
+            "tools": {},
+        },
+        "serverInfo": {
+            "name": "dynamo-mcp-rust",
+            "version": "0.1.0",
+            "offline": offline,
+            "instructions": instructions
+        }
+    });
+    io.add_method("initialize", move |_params: Params| {
+        let result = initialize_result.clone();
+        async move { Ok(result) }
     });
     
     // Handle resources/list
@@ -220,11 +868,11 @@ fn main() -> Result<()> {
     io.add_method("resources/list", move |_params: Params| {
         let index = index_for_resources.clone();
         async move {
-            let index = index.lock().unwrap();
+            let index = index.read().await;
             let mut resources = Vec::new();
-            for doc in index.documents.values() {
+            for doc in index.active_documents() {
                 resources.push(json!({
-                    "uri": format!("dynamo://docs/{}", doc.id),
+                    "uri": doc.uri(""),
                     "name": doc.title,
                     "description": format!("{} documentation from {}", doc.category, doc.repo),
                     "mimeType": "text/markdown"
@@ -235,25 +883,59 @@ fn main() -> Result<()> {
     });
     
     // Handle resources/read
+    let config = Arc::new(RwLock::new(config));
     let index_for_read = index_clone.clone();
+    let repo_manager_for_read = repo_manager_clone.clone();
+    let config_for_read = config.clone();
     io.add_method("resources/read", move |params: Params| {
         let index = index_for_read.clone();
+        let repo_manager = repo_manager_for_read.clone();
+        let config = config_for_read.clone();
         async move {
             let params: serde_json::Map<String, Value> = params.parse()?;
             let uri = params.get("uri")
                 .and_then(|v| v.as_str())
                 .ok_or_else(|| jsonrpc_core::Error::invalid_params("uri required"))?;
             
-            let index = index.lock().unwrap();
-            if let Some(doc_id) = uri.strip_prefix("dynamo://docs/") {
-                if let Some(doc) = index.documents.get(doc_id) {
-                    return Ok(json!({
-                        "contents": [{
-                            "uri": uri,
-                            "mimeType": "text/markdown",
-                            "text": doc.content
-                        }]
-                    }));
+            if let Some(path) = uri.strip_prefix("dynamo://docs/") {
+                let config = config.read().await;
+                let (uri_version, doc_id, anchor) = parse_doc_uri(path, |name| config.get(name).is_some());
+                let version = params.get("version")
+                    .and_then(|v| v.as_str())
+                    .map(|v| v.to_string())
+                    .or(uri_version);
+                let repo_name = doc_id.split('/').next().unwrap_or_default();
+                
+                let known = config.get(repo_name).is_some();
+                drop(config);
+                let version = match version {
+                    Some(version) if known => {
+                        let resolved = load_version(&index, &repo_manager, repo_name, &version)
+                            .await
+                            .map_err(|e| jsonrpc_core::Error::invalid_params(
+                                format!("Error loading version {} of {}: {}", version, repo_name, e)))?;
+                        Some(resolved)
+                    },
+                    Some(_) => return Err(jsonrpc_core::Error::invalid_params("Resource not found")),
+                    None => None,
+                };
+                
+                let index = index.read().await;
+                if let Some(doc) = index.get(doc_id, version.as_deref()) {
+                    let text = if anchor.is_empty() {
+                        Some(doc.content.as_str())
+                    } else {
+                        doc.section(anchor).map(|section| doc.section_text(section))
+                    };
+                    if let Some(text) = text {
+                        return Ok(json!({
+                            "contents": [{
+                                "uri": uri,
+                                "mimeType": "text/markdown",
+                                "text": text
+                            }]
+                        }));
+                    }
                 }
             }
             
@@ -262,70 +944,173 @@ fn main() -> Result<()> {
     });
     
     // Handle tools/list
-    io.add_method("tools/list", |_params: Params| async {
-        Ok(json!({
-            "tools": [
-                {
-                    "name": "search_docs",
-                    "description": "Search Dynamo documentation",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "query": {"type": "string", "description": "Search query"},
-                            "limit": {"type": "integer", "description": "Max results"}
-                        },
-                        "required": ["query"]
-                    }
-                },
-                {
-                    "name": "list_versions",
-                    "description": "List available versions for a repository",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "repo": {"type": "string", "description": "Repository name (dynamo or dynamo-dotfiles)"}
-                        },
-                        "required": ["repo"]
-                    }
-                },
-                {
-                    "name": "switch_version",
-                    "description": "Switch repository to a different version",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {
-                            "repo": {"type": "string", "description": "Repository name (dynamo or dynamo-dotfiles)"},
-                            "version": {"type": "string", "description": "Version to switch to (branch/tag/commit)"}
-                        },
-                        "required": ["repo", "version"]
-                    }
-                },
-                {
-                    "name": "refresh_repos",
-                    "description": "Fetch latest updates from GitHub",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {}
-                    }
-                },
-                {
-                    "name": "bootstrap_status",
-                    "description": "Check installation status",
-                    "inputSchema": {
-                        "type": "object",
-                        "properties": {}
+    let config_for_list = config.clone();
+    io.add_method("tools/list", move |_params: Params| {
+        let config = config_for_list.clone();
+        async move {
+            // Repository arguments are restricted to the configured registry
+            let config = config.read().await;
+            let repo_names = config.names().join(", ");
+            let repo_schema = json!({
+                "type": "string",
+                "enum": config.names(),
+                "description": format!("Repository name ({})", repo_names)
+            });
+            let tools = json!({
+                "tools": [
+                    {
+                        "name": "search_docs",
+                        "description": "Search Dynamo documentation",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "query": {"type": "string", "description": "Search query. Terms are ANDed; supports OR, \"quoted phrases\", -exclusion and title:, category:, repo: prefixes"},
+                                "limit": {"type": "integer", "description": "Max results"},
+                                "snippet_context": {"type": "integer", "description": "Lines of context around each match in snippets (default 1)"},
+                                "max_snippets": {"type": "integer", "description": "Max snippets per result (default 3)"},
+                                "repo": repo_schema,
+                                "version": {"type": "string", "description": "Search this version of repo instead of its active one (any version switch_version accepts); it is loaded on demand and results get version-qualified URIs"}
+                            },
+                            "required": ["query"]
+                        }
+                    },
+                    {
+                        "name": "list_versions",
+                        "description": "List available versions for a repository: branches by last commit, tags by semantic version and releases by date, newest first",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "repo": repo_schema,
+                                "filter": {"type": "string", "description": "Only list versions whose name contains this text"},
+                                "include_prereleases": {"type": "boolean", "description": "Include prerelease tags and releases (default true)"},
+                                "offset": {"type": "integer", "description": "Entries to skip in each list (default 0)"},
+                                "limit": {"type": "integer", "description": "Max entries per list (default 20)"}
+                            },
+                            "required": ["repo"]
+                        }
+                    },
+                    {
+                        "name": "switch_version",
+                        "description": "Switch repository to a different version",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "repo": repo_schema,
+                                "version": {"type": "string", "description": "Version to switch to: branch/tag/commit, latest, latest-prerelease, a semver range like ~0.3 or >=0.4,<0.5, or branch@YYYY-MM-DD"}
+                            },
+                            "required": ["repo", "version"]
+                        }
+                    },
+                    {
+                        "name": "diff_docs",
+                        "description": "List documentation files added, removed, modified or renamed between two versions, optionally with the unified diff of one file",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "repo": repo_schema,
+                                "from": {"type": "string", "description": "Old version (any version switch_version accepts)"},
+                                "to": {"type": "string", "description": "New version (any version switch_version accepts)"},
+                                "path": {"type": "string", "description": "File path relative to the repository root, or document id, to return a unified diff for"},
+                                "context": {"type": "integer", "description": "Lines of context in the unified diff (default 3)"}
+                            },
+                            "required": ["repo", "from", "to"]
+                        }
+                    },
+                    {
+                        "name": "changelog",
+                        "description": "List commits between two versions with subject, author, date and touched paths, grouped by conventional commit type",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "repo": repo_schema,
+                                "from": {"type": "string", "description": "Old version, excluded (any version switch_version accepts)"},
+                                "to": {"type": "string", "description": "New version, included (default: the active version)"},
+                                "path": {"type": "string", "description": "Only list commits touching this path prefix, e.g. docs/ or lib/llm"},
+                                "limit": {"type": "integer", "description": "Max commits (default 200, at most 5000)"}
+                            },
+                            "required": ["repo", "from"]
+                        }
+                    },
+                    {
+                        "name": "add_repo",
+                        "description": "Clone and index a new repository, and add it to the registry",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "name": {"type": "string", "description": "Name used by tools and in document ids"},
+                                "owner": {"type": "string", "description": "Owner, organization or group"},
+                                "repo": {"type": "string", "description": "Repository (default: name)"},
+                                "forge": {"type": "string", "enum": ["github", "gitlab", "gitea"], "description": "Where the repository is hosted, on the forge's public instance (default: github). Self-hosted forges, mirrors and plain git remotes are configured in the config file"},
+                                "version": {"type": "string", "description": "Version to check out (default: latest release)"},
+                                "include": {"type": "array", "items": {"type": "string"}, "description": "Globs of files to index (default: README.md, docs/**/*.md)"},
+                                "exclude": {"type": "array", "items": {"type": "string"}, "description": "Globs of files to skip"}
+                            },
+                            "required": ["name", "owner"]
+                        }
+                    },
+                    {
+                        "name": "remove_repo",
+                        "description": "Stop serving a repository and remove it from the registry",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "repo": repo_schema
+                            },
+                            "required": ["repo"]
+                        }
+                    },
+                    {
+                        "name": "refresh_repos",
+                        "description": "Fetch latest updates, fast-forward repositories that are on a branch and re-index their changed docs. Reports per repository whether the fetch succeeded, new tags and releases, updated branches and whether the active version is behind",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {}
+                        }
+                    },
+                    {
+                        "name": "cleanup_cache",
+                        "description": "Remove least recently used version worktrees from the cache",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "keep_recent": {"type": "integer", "description": "Inactive worktrees to keep (default 3)"},
+                                "max_disk_mb": {"type": "integer", "description": "Disk budget for all worktrees in MB (default DYNAMO_WORKTREE_BUDGET_MB, if set)"}
+                            }
+                        }
+                    },
+                    {
+                        "name": "rate_limit_status",
+                        "description": "Show whether GitHub requests are authenticated and how much of the API rate limit is left",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {}
+                        }
+                    },
+                    {
+                        "name": "bootstrap_status",
+                        "description": "Check installation status",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {}
+                        }
                     }
-                }
-            ]
-        }))
+                ]
+            });
+            Ok(tools)
+        }
     });
     
     // Handle tools/call
     let index_for_tools = index_clone.clone();
     let repo_manager_for_tools = repo_manager_clone.clone();
+    let config_for_tools = config.clone();
+    let use_local_for_tools = use_local;
+    let offline_for_tools = offline;
     io.add_method("tools/call", move |params: Params| {
         let index = index_for_tools.clone();
         let repo_manager = repo_manager_for_tools.clone();
+        let config = config_for_tools.clone();
+        let use_local = use_local_for_tools;
         async move {
             let params: serde_json::Map<String, Value> = params.parse()?;
             let name = params.get("name")
@@ -341,19 +1126,76 @@ fn main() -> Result<()> {
                     let limit = arguments.get("limit")
                         .and_then(|v| v.as_u64())
                         .unwrap_or(10) as usize;
+                    let mut snippet_options = SnippetOptions::default();
+                    if let Some(context) = arguments.get("snippet_context").and_then(|v| v.as_u64()) {
+                        snippet_options.context_lines = context as usize;
+                    }
+                    if let Some(max) = arguments.get("max_snippets").and_then(|v| v.as_u64()) {
+                        snippet_options.max_snippets = max as usize;
+                    }
                     
-                    let index = index.lock().unwrap();
-                    let results = index.search(query);
+                    let repo_name = arguments.get("repo").and_then(|v| v.as_str());
+                    let version = arguments.get("version").and_then(|v| v.as_str());
+                    if let Some(repo_name) = repo_name {
+                        config.read().await.require(repo_name)
+                            .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?;
+                    }
+                    
+                    // Search the active versions, or a specific version of one repository
+                    let versions: HashMap<String, String> = match (repo_name, version) {
+                        (Some(repo_name), Some(version)) => {
+                            match load_version(&index, &repo_manager, repo_name, version).await {
+                                Ok(resolved) => HashMap::from([(repo_name.to_string(), resolved)]),
+                                Err(e) => {
+                                    return Ok(json!({
+                                        "content": [{
+                                            "type": "text",
+                                            "text": format!("Error loading version {} of {}: {}", version, repo_name, e)
+                                        }],
+                                        "isError": true
+                                    }));
+                                }
+                            }
+                        },
+                        (None, Some(_)) => {
+                            return Err(jsonrpc_core::Error::invalid_params("version requires repo"));
+                        },
+                        (Some(repo_name), None) => {
+                            index.read().await.active
+                                .iter()
+                                .filter(|(name, _)| name.as_str() == repo_name)
+                                .map(|(name, active)| (name.clone(), active.clone()))
+                                .collect()
+                        },
+                        (None, None) => index.read().await.active.clone(),
+                    };
+                    
+                    let query = Query::parse(query);
+                    // Terms only searched for in titles aren't located in snippets
+                    let terms = query.content_terms();
+                    let index = index.read().await;
+                    let results = index.search(&query, &versions);
                     let results: Vec<_> = results.into_iter()
                         .take(limit)
                         .enumerate()
-                        .map(|(i, doc)| json!({
+                        .map(|(i, (doc, section, score))| json!({
                             "rank": i + 1,
+                            "score": score,
                             "id": doc.id,
+                            "uri": if version.is_some() { doc.versioned_uri(&section.anchor) } else { doc.uri(&section.anchor) },
                             "title": doc.title,
+                            "section": section.heading_path.join(" > "),
+                            "lines": [section.start_line, section.end_line],
                             "category": doc.category,
                             "repo": doc.repo,
-                            "preview": doc.content.lines().take(2).collect::<Vec<_>>().join("\n")
+                            "version": doc.version,
+                            "snippets": snippet::build_snippets(
+                                doc.section_text(section),
+                                section.start_line,
+                                section.start,
+                                &terms,
+                                snippet_options,
+                            )
                         }))
                         .collect();
                     
@@ -368,9 +1210,27 @@ fn main() -> Result<()> {
                     let repo_name = arguments.get("repo")
                         .and_then(|v| v.as_str())
                         .ok_or_else(|| jsonrpc_core::Error::invalid_params("repo required"))?;
+                    config.read().await.require(repo_name)
+                        .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?;
                     
-                    let repo_manager = repo_manager.lock().unwrap();
-                    match repo_manager.list_versions(repo_name) {
+                    let mut filter = VersionFilter {
+                        name: arguments.get("filter")
+                            .and_then(|v| v.as_str())
+                            .map(|s| s.to_string()),
+                        ..VersionFilter::default()
+                    };
+                    if let Some(include) = arguments.get("include_prereleases").and_then(|v| v.as_bool()) {
+                        filter.include_prereleases = include;
+                    }
+                    if let Some(offset) = arguments.get("offset").and_then(|v| v.as_u64()) {
+                        filter.offset = offset as usize;
+                    }
+                    if let Some(limit) = arguments.get("limit").and_then(|v| v.as_u64()) {
+                        filter.limit = limit as usize;
+                    }
+                    
+                    let repo_manager = repo_manager.read().await;
+                    match repo_manager.list_versions(repo_name, &filter).await {
                         Ok(version_info) => {
                             Ok(json!({
                                 "content": [{
@@ -397,20 +1257,56 @@ fn main() -> Result<()> {
                     let version = arguments.get("version")
                         .and_then(|v| v.as_str())
                         .ok_or_else(|| jsonrpc_core::Error::invalid_params("version required"))?;
+                    config.read().await.require(repo_name)
+                        .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?;
                     
-                    let mut repo_manager = repo_manager.lock().unwrap();
-                    match repo_manager.switch_version(repo_name, version) {
-                        Ok(path) => {
-                            // Re-index after switching version
-                            let mut index = index.lock().unwrap();
-                            index.documents.clear();
-                            let _ = index.index_from_manager(&*repo_manager);
+                    // Check out under a read lock and re-index without a lock (from the index
+                    // cache when possible), so only making it current excludes other requests
+                    let checkout = repo_manager.read().await.checkout_version(repo_name, version).await;
+                    let switched = match checkout {
+                        Ok(checkout) => {
+                            let snapshot = build_version(&index, repo_name, &checkout.version, &checkout.path, checkout.commit.as_deref()).await;
+                            // Switch the repository and its index together, so concurrent switches
+                            // can't leave them at different versions
+                            let mut repo_manager = repo_manager.write().await;
+                            let mut index = index.write().await;
+                            repo_manager.switch_version(repo_name, &checkout).map(|_| {
+                                // The previously active version stays loaded for version-qualified reads
+                                match snapshot {
+                                    Ok(Some(snapshot)) => index.install(repo_name, &checkout.version, &checkout.path, snapshot),
+                                    Ok(None) => {},
+                                    Err(e) => warn!("Failed to re-index {}: {}", repo_name, e),
+                                }
+                                index.activate(repo_name, &checkout.version);
+                                checkout
+                            })
+                        },
+                        Err(e) => Err(e),
+                    };
+                    match switched {
+                        Ok(checkout) => {
+                            let resolved = checkout.version;
+                            let index = index.read().await;
+                            
+                            let mut text = if resolved == version {
+                                format!("Switched {} to version {} at {:?}", repo_name, version, checkout.path)
+                            } else {
+                                format!("Switched {} to version {} (resolved from {}) at {:?}", repo_name, resolved, version, checkout.path)
+                            };
+                            text.push_str(&format!("\nRe-indexed {} documents", index.document_count(repo_name, &resolved)));
+                            let prefix = format!("{}/", repo_name);
+                            let collisions = index.collisions
+                                .iter()
+                                .filter(|c| c.id.starts_with(&prefix) && c.version == resolved);
+                            for collision in collisions {
+                                text.push_str(&format!("\nSkipped {} (its id differs only by case from {}, used by {})",
+                                    collision.skipped, collision.id, collision.kept));
+                            }
                             
                             Ok(json!({
                                 "content": [{
                                     "type": "text",
-                                    "text": format!("Switched {} to version {} at {:?}\nRe-indexed {} documents", 
-                                        repo_name, version, path, index.documents.len())
+                                    "text": text
                                 }]
                             }))
                         },
@@ -425,14 +1321,240 @@ fn main() -> Result<()> {
                         }
                     }
                 },
+                "diff_docs" => {
+                    let repo_name = arguments.get("repo")
+                        .and_then(|v| v.as_str())
+                        .ok_or_else(|| jsonrpc_core::Error::invalid_params("repo required"))?;
+                    let from = arguments.get("from")
+                        .and_then(|v| v.as_str())
+                        .ok_or_else(|| jsonrpc_core::Error::invalid_params("from required"))?;
+                    let to = arguments.get("to")
+                        .and_then(|v| v.as_str())
+                        .ok_or_else(|| jsonrpc_core::Error::invalid_params("to required"))?;
+                    let context = arguments.get("context")
+                        .and_then(|v| v.as_u64())
+                        .unwrap_or(3) as usize;
+                    let rules = config.read().await.require(repo_name)
+                        .and_then(|repo| repo.index_rules())
+                        .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?;
+                    
+                    // Accept document ids (`{repo}/{path without .md}`) as well as paths
+                    let path = arguments.get("path")
+                        .and_then(|v| v.as_str())
+                        .map(|path| match path.strip_prefix(&format!("{}/", repo_name)) {
+                            Some(relative) if !path.ends_with(".md") => format!("{}.md", relative),
+                            _ => path.to_string(),
+                        });
+                    
+                    let repo_manager = repo_manager.read().await;
+                    let is_doc = |path: &str| path.ends_with(".md") && rules.matches(path);
+                    match repo_manager.diff_docs(repo_name, from, to, is_doc, path.as_deref(), context).await {
+                        Ok(diff) => {
+                            Ok(json!({
+                                "content": [{
+                                    "type": "text",
+                                    "text": serde_json::to_string_pretty(&diff).unwrap()
+                                }]
+                            }))
+                        },
+                        Err(e) => {
+                            Ok(json!({
+                                "content": [{
+                                    "type": "text",
+                                    "text": format!("Error diffing docs: {}", e)
+                                }],
+                                "isError": true
+                            }))
+                        }
+                    }
+                },
+                "changelog" => {
+                    let repo_name = arguments.get("repo")
+                        .and_then(|v| v.as_str())
+                        .ok_or_else(|| jsonrpc_core::Error::invalid_params("repo required"))?;
+                    let from = arguments.get("from")
+                        .and_then(|v| v.as_str())
+                        .ok_or_else(|| jsonrpc_core::Error::invalid_params("from required"))?;
+                    let path = arguments.get("path").and_then(|v| v.as_str());
+                    let limit = arguments.get("limit")
+                        .and_then(|v| v.as_u64())
+                        .unwrap_or(200)
+                        .try_into()
+                        .unwrap_or(usize::MAX);
+                    config.read().await.require(repo_name)
+                        .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?;
+                    
+                    let to = arguments.get("to").and_then(|v| v.as_str());
+                    let repo_manager = repo_manager.read().await;
+                    match repo_manager.changelog(repo_name, from, to, path, limit).await {
+                        Ok(changelog) => {
+                            Ok(json!({
+                                "content": [{
+                                    "type": "text",
+                                    "text": serde_json::to_string_pretty(&changelog).unwrap()
+                                }]
+                            }))
+                        },
+                        Err(e) => {
+                            Ok(json!({
+                                "content": [{
+                                    "type": "text",
+                                    "text": format!("Error reading changelog: {}", e)
+                                }],
+                                "isError": true
+                            }))
+                        }
+                    }
+                },
+                "add_repo" => {
+                    let repo_name = arguments.get("name")
+                        .and_then(|v| v.as_str())
+                        .ok_or_else(|| jsonrpc_core::Error::invalid_params("name required"))?;
+                    let owner = arguments.get("owner")
+                        .and_then(|v| v.as_str())
+                        .ok_or_else(|| jsonrpc_core::Error::invalid_params("owner required"))?;
+                    let optional = |key: &str| arguments.get(key)
+                        .and_then(|v| v.as_str())
+                        .map(|s| s.to_string());
+                    let globs = |key: &str| arguments.get(key)
+                        .and_then(|v| v.as_array())
+                        .map(|globs| globs.iter().filter_map(|g| g.as_str()).map(|g| g.to_string()).collect())
+                        .unwrap_or_default();
+                    let repo_config = RepoConfig {
+                        name: repo_name.to_string(),
+                        owner: owner.to_string(),
+                        repo: optional("repo"),
+                        version: optional("version"),
+                        include: globs("include"),
+                        exclude: globs("exclude"),
+                        categories: BTreeMap::new(),
+                        forge: optional("forge")
+                            .map(|forge| forge.parse())
+                            .transpose()
+                            .map_err(|e: anyhow::Error| jsonrpc_core::Error::invalid_params(e.to_string()))?,
+                        // Only the config file may point at other hosts, which receive the forge's token
+                        api_url: None,
+                        clone_url: None,
+                    };
+                    
+                    // Validate before cloning anything
+                    let rules = config.read().await.check_new(&repo_config)
+                        .and_then(|_| repo_config.index_rules())
+                        .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?;
+                    
+                    // Clone under a read lock; the repository is only managed once it is registered
+                    let prepared = repo_manager.read().await.prepare_repo(
+                        repo_name,
+                        owner,
+                        repo_config.repo_name(),
+                        &repo_config.forge_config(),
+                        repo_config.version().as_deref(),
+                        use_local,
+                    ).await;
+                    
+                    // Persist the registration; the in-memory registry only changes once it is saved
+                    let added = match prepared {
+                        Ok(prepared) => {
+                            let mut config = config.write().await;
+                            let mut updated = config.clone();
+                            updated.add(repo_config)
+                                .and_then(|_| updated.save())
+                                .map(|_| {
+                                    *config = updated;
+                                    prepared
+                                })
+                        },
+                        Err(e) => Err(e),
+                    };
+                    
+                    match added {
+                        Ok(prepared) => {
+                            let version = prepared.version().to_string();
+                            let path = repo_manager.write().await.insert_repo(prepared);
+                            let commit = repo_manager.read().await.worktree_commit(repo_name).await;
+                            index.write().await.add_rules(repo_name, rules);
+                            let snapshot = build_version(&index, repo_name, &version, &path, commit.as_deref()).await;
+                            let mut index = index.write().await;
+                            match snapshot {
+                                Ok(Some(snapshot)) => index.install(repo_name, &version, &path, snapshot),
+                                Ok(None) => {},
+                                Err(e) => warn!("Failed to index {}: {}", repo_name, e),
+                            }
+                            index.activate(repo_name, &version);
+                            let count = index.document_count(repo_name, &version);
+                            drop(index);
+                            transport::notify("notifications/resources/list_changed");
+                            
+                            Ok(json!({
+                                "content": [{
+                                    "type": "text",
+                                    "text": format!("Added {} at version {} in {:?}\nIndexed {} documents",
+                                        repo_name, version, path, count)
+                                }]
+                            }))
+                        },
+                        Err(e) => {
+                            Ok(json!({
+                                "content": [{
+                                    "type": "text",
+                                    "text": format!("Error adding repository: {}", e)
+                                }],
+                                "isError": true
+                            }))
+                        }
+                    }
+                },
+                "remove_repo" => {
+                    let repo_name = arguments.get("repo")
+                        .and_then(|v| v.as_str())
+                        .ok_or_else(|| jsonrpc_core::Error::invalid_params("repo required"))?;
+                    
+                    let removed = {
+                        let mut config = config.write().await;
+                        config.require(repo_name)
+                            .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?;
+                        let mut updated = config.clone();
+                        updated.remove(repo_name)
+                            .and_then(|_| updated.save())
+                            .map(|_| *config = updated)
+                    };
+                    
+                    match removed {
+                        Ok(()) => {
+                            repo_manager.write().await.remove_repo(repo_name);
+                            let mut index = index.write().await;
+                            let before = index.documents.len();
+                            index.forget_repo(repo_name);
+                            let count = before - index.documents.len();
+                            drop(index);
+                            transport::notify("notifications/resources/list_changed");
+                            
+                            Ok(json!({
+                                "content": [{
+                                    "type": "text",
+                                    "text": format!("Removed {} and its {} documents\nIts worktrees can be reclaimed with cleanup_cache",
+                                        repo_name, count)
+                                }]
+                            }))
+                        },
+                        Err(e) => {
+                            Ok(json!({
+                                "content": [{
+                                    "type": "text",
+                                    "text": format!("Error removing repository: {}", e)
+                                }],
+                                "isError": true
+                            }))
+                        }
+                    }
+                },
                 "refresh_repos" => {
-                    let mut repo_manager = repo_manager.lock().unwrap();
-                    match repo_manager.refresh() {
-                        Ok(_) => {
+                    match refresh::refresh_repos(&index, &repo_manager).await {
+                        Ok(reports) => {
                             Ok(json!({
                                 "content": [{
                                     "type": "text",
-                                    "text": "Successfully refreshed repositories"
+                                    "text": serde_json::to_string_pretty(&reports).unwrap()
                                 }]
                             }))
                         },
@@ -447,6 +1569,71 @@ fn main() -> Result<()> {
                         }
                     }
                 },
+                "cleanup_cache" => {
+                    let keep_recent = arguments.get("keep_recent")
+                        .and_then(|v| v.as_u64())
+                        .unwrap_or(3) as usize;
+                    let max_disk_mb = arguments.get("max_disk_mb")
+                        .and_then(|v| v.as_u64())
+                        .or_else(|| env::var("DYNAMO_WORKTREE_BUDGET_MB").ok().and_then(|v| v.parse().ok()));
+                    
+                    // Leases keep worktrees in use safe, so cleanup needs no lock
+                    let repo_manager = repo_manager.read().await.clone();
+                    match repo_manager.cleanup_old_worktrees(keep_recent, max_disk_mb.map(|mb| mb.saturating_mul(1024 * 1024))).await {
+                        Ok(report) => {
+                            Ok(json!({
+                                "content": [{
+                                    "type": "text",
+                                    "text": serde_json::to_string_pretty(&report).unwrap()
+                                }]
+                            }))
+                        },
+                        Err(e) => {
+                            Ok(json!({
+                                "content": [{
+                                    "type": "text",
+                                    "text": format!("Error cleaning up cache: {}", e)
+                                }],
+                                "isError": true
+                            }))
+                        }
+                    }
+                },
+                "rate_limit_status" => {
+                    // Querying /rate_limit is free, so report the live numbers when possible
+                    let current = if offline_for_tools {
+                        None
+                    } else {
+                        let client = config.read().await.repos.iter()
+                            .find(|repo| repo.forge.unwrap_or_default() == forge::ForgeKind::GitHub)
+                            .map(|repo| github::GitHubClient::new(&repo.owner, repo.repo_name()).with_config(&repo.forge_config()));
+                        match client {
+                            Some(client) => Some(client.rate_limit().await),
+                            None => None,
+                        }
+                    };
+                    
+                    let mut status = json!({
+                        "authenticated": github::token().is_some(),
+                        "offline": offline_for_tools,
+                        "last_response": github::last_rate_limit(),
+                    });
+                    match current {
+                        Some(Ok(rate_limit)) => {
+                            status["reset_at"] = json!(rate_limit.reset_at());
+                            status["current"] = json!(rate_limit);
+                        },
+                        Some(Err(e)) => status["error"] = json!(e.to_string()),
+                        None => {},
+                    }
+                    
+                    Ok(json!({
+                        "content": [{
+                            "type": "text",
+                            "text": serde_json::to_string_pretty(&status).unwrap()
+                        }]
+                    }))
+                },
                 "bootstrap_status" => {
                     let tools = vec!["chezmoi", "mise", "fish", "hx", "zellij", "starship", "rg", "eza"];
                     let mut status = HashMap::new();
@@ -455,6 +1642,7 @@ fn main() -> Result<()> {
                         let exists = Command::new("which")
                             .arg(tool)
                             .output()
+                            .await
                             .map(|o| o.status.success())
                             .unwrap_or(false);
                         status.insert(tool, exists);
@@ -473,12 +1661,220 @@ fn main() -> Result<()> {
     });
     
     // Run the server
-    let server = ServerBuilder::new(io);
-    let _server_handle = server.build();
-    
     info!("MCP server running on stdio");
-    // Keep the server running
-    std::thread::park();
+    transport::serve_stdio(io).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    
+    /// Index of a repository `r` at version `main`, made of the given files
+    fn index_files(dir: &TempDir, files: &[(&str, &str)]) -> DocumentIndex {
+        let repo = dir.path().join("repo");
+        for (path, content) in files {
+            let path = repo.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        let rules = IndexRules::new(&["**/*.md".to_string()], &[], &BTreeMap::new()).unwrap();
+        let cache = IndexCache::new(dir.path().join("index"));
+        let mut index = DocumentIndex::new(HashMap::from([("r".to_string(), rules)]), cache);
+        index.load_repo("r", "main", &repo, None).unwrap();
+        index.activate("r", "main");
+        index
+    }
+    
+    /// `id#anchor` of each section found, best first
+    fn search(index: &DocumentIndex, query: &str) -> Vec<String> {
+        let versions = HashMap::from([("r".to_string(), "main".to_string())]);
+        index.search(&Query::parse(query), &versions)
+            .into_iter()
+            .map(|(doc, section, _)| format!("{}#{}", doc.id, section.anchor))
+            .collect()
+    }
+    
+    #[test]
+    fn search_matches_terms_spread_over_sections() {
+        let dir = TempDir::new("search-spread");
+        let index = index_files(&dir, &[
+            ("a.md", "# A\n## Intro\ninstall it\n## Usage\nrun the server\n"),
+        ]);
+        assert_eq!(search(&index, "install"), ["r/a#intro"]);
+        assert_eq!(search(&index, "install server").len(), 2);
+        assert!(search(&index, "install missing").is_empty());
+    }
+    
+    #[test]
+    fn search_excludes_whole_documents() {
+        let dir = TempDir::new("search-exclude");
+        let index = index_files(&dir, &[
+            ("a.md", "# A\n## Intro\ninstall it\n## Usage\nrun the server\n"),
+            ("b.md", "# B\n## Intro\ninstall it too\n"),
+        ]);
+        assert_eq!(search(&index, "install -server"), ["r/b#intro"]);
+        assert_eq!(search(&index, "install -\"the server\""), ["r/b#intro"]);
+        assert_eq!(search(&index, "install -server OR server").len(), 2);
+    }
+    
+    #[test]
+    fn search_boosts_the_document_title_once() {
+        let dir = TempDir::new("search-title");
+        let index = index_files(&dir, &[
+            ("planner.md", "# Planner\nScales workers.\n## Install\npip install\n## Config\nset flags\n## Debug\nread logs\n"),
+            ("scaling.md", "# Autoscaling\nThe planner adds workers when the planner sees load.\n"),
+        ]);
+        assert_eq!(search(&index, "planner"), ["r/planner#planner", "r/scaling#autoscaling"]);
+        assert_eq!(search(&index, "title:planner"), ["r/planner#planner"]);
+        assert_eq!(search(&index, "title:install"), ["r/planner#install"]);
+        
+        // Without a top-level heading the title comes from the file name and goes to
+        // the first section
+        let dir = TempDir::new("search-fallback-title");
+        let index = index_files(&dir, &[("kv_router.md", "Intro text\n## Setup\nsteps\n")]);
+        let doc = index.get("r/kv_router", None).unwrap();
+        let titles: Vec<String> = doc.sections.iter().map(|s| doc.section_title(s)).collect();
+        assert_eq!(titles, ["kv router", "Setup"]);
+    }
     
-    Ok(())
+    #[test]
+    fn restores_snapshots_relative_to_the_checkout() {
+        let dir = TempDir::new("snapshot-rebase");
+        let mut index = index_files(&dir, &[("docs/a.md", "# A\nalpha\n")]);
+        let repo = dir.path().join("repo");
+        let rules = index.rules["r"].clone();
+        
+        let snapshot = index.snapshot("r", "main", &repo, &rules);
+        assert_eq!(snapshot.documents[0].path, "docs/a.md");
+        
+        // Another checkout of the same commit, e.g. a tag's worktree
+        let other = dir.path().join("other");
+        index.install("r", "v1", &other, snapshot);
+        let doc = index.get("r/docs/a", Some("v1")).unwrap();
+        assert_eq!(Path::new(&doc.path), other.join("docs/a.md"));
+        let versions = HashMap::from([("r".to_string(), "v1".to_string())]);
+        assert_eq!(index.search(&Query::parse("alpha"), &versions).len(), 1);
+    }
+    
+    #[test]
+    fn loads_persisted_snapshots_by_commit() {
+        let dir = TempDir::new("snapshot-commit");
+        let mut index = index_files(&dir, &[("a.md", "# A\nalpha\n")]);
+        let repo = dir.path().join("repo");
+        index.load_repo("r", "main", &repo, Some("c1")).unwrap();
+        
+        // The snapshot is used without reading the (here missing) files again
+        let other = dir.path().join("other");
+        index.load_repo("r", "v1", &other, Some("c1")).unwrap();
+        assert_eq!(Path::new(&index.get("r/a", Some("v1")).unwrap().path), other.join("a.md"));
+        index.load_repo("r", "v2", &other, Some("c2")).unwrap();
+        assert_eq!(index.document_count("r", "v2"), 0);
+    }
+    
+    #[test]
+    fn loads_versions_side_by_side() {
+        let dir = TempDir::new("multi-version");
+        let mut index = index_files(&dir, &[("a.md", "# A\ncurrent text\n")]);
+        let old = dir.path().join("old");
+        fs::create_dir_all(&old).unwrap();
+        fs::write(old.join("a.md"), "# A\nold text\n").unwrap();
+        fs::write(old.join("b.md"), "# B\nremoved later\n").unwrap();
+        index.load_repo("r", "v1", &old, None).unwrap();
+        
+        assert_eq!(index.get("r/a", None).unwrap().content, "# A\ncurrent text\n");
+        assert_eq!(index.get("r/a", Some("v1")).unwrap().content, "# A\nold text\n");
+        assert!(index.get("r/b", None).is_none());
+        assert_eq!(index.get("r/b", Some("v1")).unwrap().versioned_uri("b"), "dynamo://docs/v1/r/b#b");
+        
+        // Searches cover the versions asked for, not the active one
+        assert_eq!(search(&index, "text"), ["r/a#a"]);
+        let versions = HashMap::from([("r".to_string(), "v1".to_string())]);
+        let found: Vec<String> = index.search(&Query::parse("removed"), &versions)
+            .into_iter()
+            .map(|(doc, _, _)| doc.key())
+            .collect();
+        assert_eq!(found, ["v1/r/b"]);
+        assert_eq!(index.active_version("r"), Some("main"));
+    }
+    
+    #[test]
+    fn unloads_least_recently_used_versions_but_never_the_active_one() {
+        let dir = TempDir::new("evict-versions");
+        let mut index = index_files(&dir, &[("a.md", "# A\ntext\n")]);
+        let repo = dir.path().join("repo");
+        for n in 1..=MAX_LOADED_VERSIONS {
+            index.load_repo("r", &format!("v{}", n), &repo, None).unwrap();
+        }
+        
+        // `main` is the least recently used, yet active, so `v1` goes instead
+        assert!(index.is_loaded("r", "main"));
+        assert!(!index.is_loaded("r", "v1"));
+        assert!(index.get("r/a", Some("v1")).is_none());
+        assert!(!index.leases.contains_key(&("r".to_string(), "v1".to_string())));
+        
+        index.touch("r", "v2");
+        index.load_repo("r", "v6", &repo, None).unwrap();
+        assert!(!index.is_loaded("r", "v3"));
+        assert!(index.is_loaded("r", "v2"));
+        assert_eq!(index.loaded.len(), MAX_LOADED_VERSIONS);
+        assert_eq!(index.get("r/a", None).unwrap().version, "main");
+    }
+    
+    #[test]
+    fn parses_document_uris() {
+        let is_repo = |name: &str| name == "dynamo" || name == "dynamo-dotfiles";
+        assert_eq!(parse_doc_uri("dynamo/docs/README", is_repo), (None, "dynamo/docs/README", ""));
+        assert_eq!(
+            parse_doc_uri("v0.4.0/dynamo/docs/README#install", is_repo),
+            (Some("v0.4.0".to_string()), "dynamo/docs/README", "install")
+        );
+        assert_eq!(
+            parse_doc_uri("release%2F1.0/dynamo-dotfiles/README", is_repo),
+            (Some("release/1.0".to_string()), "dynamo-dotfiles/README", "")
+        );
+        
+        // Round trips through the URIs documents advertise
+        let mut doc = Document {
+            id: "dynamo/docs/README".to_string(),
+            title: "Docs".to_string(),
+            path: "docs/README.md".to_string(),
+            content: String::new(),
+            category: "getting_started".to_string(),
+            repo: "dynamo".to_string(),
+            version: "feature/x%y".to_string(),
+            sections: Vec::new(),
+        };
+        let uri = doc.versioned_uri("usage");
+        let (version, id, anchor) = parse_doc_uri(uri.strip_prefix("dynamo://docs/").unwrap(), is_repo);
+        assert_eq!((version.as_deref(), id, anchor), (Some("feature/x%y"), "dynamo/docs/README", "usage"));
+        doc.version = "main".to_string();
+        assert_eq!(parse_doc_uri(doc.uri("").strip_prefix("dynamo://docs/").unwrap(), is_repo).0, None);
+    }
+    
+    #[test]
+    fn reports_ids_that_differ_only_by_case() {
+        let dir = TempDir::new("id-case");
+        let mut index = index_files(&dir, &[
+            ("docs/README.md", "# Upper\n"),
+            ("docs/readme.md", "# Lower\n"),
+            ("docs/guides/a/README.md", "# A\n"),
+            ("docs/guides/b/README.md", "# B\n"),
+        ]);
+        assert_eq!(index.document_count("r", "main"), 3);
+        assert_eq!(index.get("r/docs/README", None).unwrap().title, "Upper");
+        assert!(index.get("r/docs/readme", None).is_none());
+        assert_eq!(index.collisions.len(), 1);
+        assert_eq!(index.collisions[0].id, "r/docs/README");
+        assert!(index.collisions[0].skipped.ends_with("docs/readme.md"));
+        
+        // Changing the skipped file doesn't report it twice
+        let repo = dir.path().join("repo");
+        assert!(!index.update_path("r", &repo, &repo.join("docs/readme.md")).unwrap());
+        assert_eq!(index.collisions.len(), 1);
+        
+        index.remove_version("r", "main");
+        assert!(index.collisions.is_empty());
+        assert!(index.folded_keys.is_empty());
+    }
 }
@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// A heading-delimited section of a markdown document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Section {
+    /// GitHub-style anchor for the heading, empty for text before the first heading
+    pub anchor: String,
+    /// Headings from the top level down to and including this section's heading
+    pub heading_path: Vec<String>,
+    /// Heading level (1-6), or 0 for text before the first heading
+    pub level: usize,
+    /// First line of the section (1-based, inclusive)
+    pub start_line: usize,
+    /// Last line of the section (1-based, inclusive)
+    pub end_line: usize,
+    /// Byte range of the section within the document content
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Split markdown into sections at ATX headings (`#` .. `######`).
+///
+/// Headings inside fenced code blocks are ignored. Text before the first heading
+/// becomes a section with an empty anchor unless it is blank. Repeated headings get
+/// anchors suffixed `-1`, `-2`, ... skipping any already taken by another heading.
+pub fn split_sections(content: &str) -> Vec<Section> {
+    let mut sections: Vec<Section> = Vec::new();
+    let mut path: Vec<(usize, String)> = Vec::new();
+    let mut anchor_counts: HashMap<String, usize> = HashMap::new();
+    let mut anchors: HashSet<String> = HashSet::new();
+    let mut fence: Option<(char, usize)> = None;
+    let mut offset = 0;
+    let mut line_no = 0;
+
+    let mut current = Section {
+        anchor: String::new(),
+        heading_path: Vec::new(),
+        level: 0,
+        start_line: 1,
+        end_line: 0,
+        start: 0,
+        end: 0,
+    };
+
+    for line in content.split_inclusive('\n') {
+        line_no += 1;
+        let line_start = offset;
+        offset += line.len();
+
+        let trimmed = line.trim_start();
+        if let Some((marker, len)) = fence {
+            // Only a run of the same character, at least as long and with nothing after
+            // it, closes the block
+            let closes = parse_fence(trimmed).is_some_and(|(c, n)| c == marker && n >= len)
+                && trimmed.trim_start_matches(marker).trim().is_empty();
+            if closes {
+                fence = None;
+            }
+        } else if let Some(opening) = parse_fence(trimmed) {
+            fence = Some(opening);
+        } else if let Some((level, heading)) = parse_heading(line) {
+            current.end = line_start;
+            current.end_line = line_no - 1;
+            if !current.heading_path.is_empty() || !content[..line_start].trim().is_empty() {
+                sections.push(current);
+            }
+
+            while path.last().is_some_and(|(l, _)| *l >= level) {
+                path.pop();
+            }
+            path.push((level, heading.clone()));
+
+            let mut slug = slugify(&heading);
+            if slug.is_empty() {
+                slug = "section".to_string();
+            }
+            let count = anchor_counts.entry(slug.clone()).or_insert(0);
+            let mut anchor = slug.clone();
+            while anchors.contains(&anchor) {
+                *count += 1;
+                anchor = format!("{}-{}", slug, count);
+            }
+            anchors.insert(anchor.clone());
+
+            current = Section {
+                anchor,
+                heading_path: path.iter().map(|(_, h)| h.clone()).collect(),
+                level,
+                start_line: line_no,
+                end_line: 0,
+                start: line_start,
+                end: 0,
+            };
+        }
+    }
+
+    current.end = content.len();
+    current.end_line = line_no.max(current.start_line);
+    if !current.heading_path.is_empty() || !content[current.start..].trim().is_empty() {
+        sections.push(current);
+    }
+    sections
+}
+
+/// Text of the first top-level (`#`) heading, if any
+pub fn title(content: &str) -> Option<String> {
+    split_sections(content)
+        .into_iter()
+        .find(|s| s.level == 1)
+        .and_then(|s| s.heading_path.into_iter().next())
+        .filter(|t| !t.is_empty())
+}
+
+/// GitHub-style heading anchor: lowercase, punctuation dropped, spaces to hyphens
+pub fn slugify(heading: &str) -> String {
+    heading
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Character and length of a code fence opening or closing line (three or more
+/// backticks or tildes)
+fn parse_fence(line: &str) -> Option<(char, usize)> {
+    let marker = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = line.chars().take_while(|c| *c == marker).count();
+    (len >= 3).then_some((marker, len))
+}
+
+fn parse_heading(line: &str) -> Option<(usize, String)> {
+    let line = line.trim_end();
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let line = &line[indent..];
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &line[level..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let text = rest.trim().trim_end_matches('#').trim_end();
+    Some((level, text.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anchors(content: &str) -> Vec<String> {
+        split_sections(content).into_iter().map(|s| s.anchor).collect()
+    }
+
+    #[test]
+    fn splits_at_headings_with_paths_lines_and_offsets() {
+        let content = "Intro text\n# Guide\nBody\n## Install\nSteps\n### Linux\napt\n## Usage\nRun\n";
+        let sections = split_sections(content);
+        let summary: Vec<_> = sections
+            .iter()
+            .map(|s| (s.anchor.as_str(), s.heading_path.join(" > "), s.level, s.start_line, s.end_line))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("", String::new(), 0, 1, 1),
+                ("guide", "Guide".to_string(), 1, 2, 3),
+                ("install", "Guide > Install".to_string(), 2, 4, 5),
+                ("linux", "Guide > Install > Linux".to_string(), 3, 6, 7),
+                ("usage", "Guide > Usage".to_string(), 2, 8, 9),
+            ]
+        );
+        for section in &sections {
+            let text = &content[section.start..section.end];
+            assert_eq!(text.lines().count(), section.end_line - section.start_line + 1);
+        }
+        assert_eq!(&content[sections[2].start..sections[2].end], "## Install\nSteps\n");
+        assert_eq!(sections.last().unwrap().end, content.len());
+    }
+
+    #[test]
+    fn skips_blank_preamble_and_non_headings() {
+        let content = "\n\n#hashtag\n    # indented code\n# Title #\n";
+        let sections = split_sections(content);
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[1].heading_path, ["Title"]);
+
+        let sections = split_sections("\n\n# Title\n");
+        assert_eq!(sections.len(), 1);
+        assert_eq!((sections[0].anchor.as_str(), sections[0].start_line), ("title", 3));
+    }
+
+    #[test]
+    fn ignores_headings_in_fenced_code() {
+        let content = "# A\n```sh\n# comment\n```\n# B\n";
+        assert_eq!(anchors(content), ["a", "b"]);
+    }
+
+    #[test]
+    fn closes_fences_only_on_matching_markers() {
+        // A tilde line doesn't close a backtick fence, and a shorter run doesn't close
+        // a longer one
+        let content = "# A\n````md\n~~~\n```\n# inside\n````\n# B\n";
+        assert_eq!(anchors(content), ["a", "b"]);
+
+        // A fence line with an info string doesn't close the block
+        let content = "# A\n```\n```rust\n# inside\n```\n# B\n";
+        assert_eq!(anchors(content), ["a", "b"]);
+    }
+
+    #[test]
+    fn deduplicates_anchors_without_collisions() {
+        assert_eq!(
+            anchors("# Setup\n# Setup\n# Setup\n"),
+            ["setup", "setup-1", "setup-2"]
+        );
+        assert_eq!(
+            anchors("# Setup 1\n# Setup\n# Setup\n"),
+            ["setup-1", "setup", "setup-2"]
+        );
+        assert_eq!(
+            anchors("# Setup\n# Setup\n# Setup 1\n"),
+            ["setup", "setup-1", "setup-1-1"]
+        );
+        assert_eq!(anchors("# !!!\n# ???\n"), ["section", "section-1"]);
+    }
+
+    #[test]
+    fn slugifies_like_github() {
+        assert_eq!(slugify(" KV-Cache: Routing & Reuse "), "kv-cache-routing--reuse");
+        assert_eq!(slugify("snake_case API"), "snake_case-api");
+    }
+
+    #[test]
+    fn title_is_the_first_top_level_heading() {
+        assert_eq!(title("## Sub\n# Main\n# Other\n").as_deref(), Some("Main"));
+        assert_eq!(title("## Only sub\n"), None);
+        assert_eq!(title("#\n"), None);
+    }
+}
@@ -0,0 +1,427 @@
+use std::cell::OnceCell;
+
+use crate::search::{tokenize, SearchIndex};
+
+/// Document field a query clause is restricted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// Title or content
+    Any,
+    Title,
+    Category,
+    Repo,
+}
+
+impl Field {
+    fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix.to_lowercase().as_str() {
+            "title" => Some(Field::Title),
+            "category" => Some(Field::Category),
+            "repo" => Some(Field::Repo),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Matcher {
+    /// A single token
+    Term(String),
+    /// Consecutive tokens
+    Phrase(Vec<String>),
+    /// Exact (case-insensitive) value, used for category and repo
+    Exact(String),
+}
+
+/// The fields of a searchable unit, or of several together, that a query is evaluated
+/// against
+pub struct Target<'a> {
+    /// Keys of the units in the search index
+    pub keys: &'a [String],
+    pub title: &'a str,
+    pub content: &'a str,
+    pub category: &'a str,
+    pub repo: &'a str,
+    /// Title and content tokens, once a phrase needs them
+    tokens: OnceCell<(Vec<String>, Vec<String>)>,
+}
+
+impl<'a> Target<'a> {
+    pub fn new(keys: &'a [String], title: &'a str, content: &'a str, category: &'a str, repo: &'a str) -> Self {
+        Self {
+            keys,
+            title,
+            content,
+            category,
+            repo,
+            tokens: OnceCell::new(),
+        }
+    }
+
+    fn title_tokens(&self) -> &[String] {
+        &self.tokens().0
+    }
+
+    fn content_tokens(&self) -> &[String] {
+        &self.tokens().1
+    }
+
+    fn tokens(&self) -> &(Vec<String>, Vec<String>) {
+        self.tokens
+            .get_or_init(|| (tokenize(self.title), tokenize(self.content)))
+    }
+
+    fn in_title(&self, index: &SearchIndex, term: &str) -> bool {
+        self.keys.iter().any(|key| index.in_title(key, term))
+    }
+
+    fn in_document(&self, index: &SearchIndex, term: &str) -> bool {
+        self.keys.iter().any(|key| index.in_document(key, term))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Clause {
+    pub field: Field,
+    pub matcher: Matcher,
+    pub negated: bool,
+}
+
+/// Parsed search query in disjunctive normal form.
+///
+/// Whitespace-separated clauses are implicitly ANDed; the `OR` keyword starts a
+/// new group, and a document matches when any group matches. Supported clause
+/// syntax:
+///
+/// - `word` — term in the title or content
+/// - `"some phrase"` — consecutive terms in the title or content
+/// - `-word`, `-"some phrase"` — exclude documents matching the clause
+/// - `title:word`, `title:"some phrase"` — restrict to the title
+/// - `category:guide`, `repo:dynamo` — exact match on the document field
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    pub groups: Vec<Vec<Clause>>,
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Self {
+        let mut groups = vec![Vec::new()];
+        let mut chars = input.chars().peekable();
+
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            let Some(&first) = chars.peek() else {
+                break;
+            };
+
+            let negated = first == '-';
+            if negated {
+                chars.next();
+            }
+
+            let (field, value, quoted) = if chars.next_if_eq(&'"').is_some() {
+                (Field::Any, read_quoted(&mut chars), true)
+            } else {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '"') {
+                    word.push(c);
+                }
+                match word.split_once(':') {
+                    Some((prefix, rest)) => match Field::from_prefix(prefix) {
+                        Some(field) if rest.is_empty() && chars.next_if_eq(&'"').is_some() => {
+                            (field, read_quoted(&mut chars), true)
+                        }
+                        Some(field) => (field, rest.to_string(), false),
+                        None => (Field::Any, word, false),
+                    },
+                    None => (Field::Any, word, false),
+                }
+            };
+
+            if !negated && !quoted && field == Field::Any {
+                match value.as_str() {
+                    "OR" => {
+                        groups.push(Vec::new());
+                        continue;
+                    }
+                    "AND" => continue,
+                    _ => {}
+                }
+            }
+
+            let matcher = match field {
+                Field::Category | Field::Repo => {
+                    let value = value.trim().to_lowercase();
+                    if value.is_empty() {
+                        continue;
+                    }
+                    Matcher::Exact(value)
+                }
+                Field::Any | Field::Title => {
+                    let mut tokens = tokenize(&value);
+                    match tokens.len() {
+                        0 => continue,
+                        1 => Matcher::Term(tokens.remove(0)),
+                        _ => Matcher::Phrase(tokens),
+                    }
+                }
+            };
+
+            groups.last_mut().unwrap().push(Clause {
+                field,
+                matcher,
+                negated,
+            });
+        }
+
+        groups.retain(|group| !group.is_empty());
+        Self { groups }
+    }
+
+    /// Terms from positive text clauses, used for ranking
+    pub fn scoring_terms(&self) -> Vec<String> {
+        self.positive_terms(|_| true)
+    }
+
+    /// Terms from positive clauses that can match the content, i.e. not restricted to
+    /// the title, used for snippets
+    pub fn content_terms(&self) -> Vec<String> {
+        self.positive_terms(|field| field == Field::Any)
+    }
+
+    fn positive_terms(&self, field: impl Fn(Field) -> bool) -> Vec<String> {
+        let mut terms: Vec<String> = self
+            .groups
+            .iter()
+            .flatten()
+            .filter(|clause| !clause.negated && field(clause.field))
+            .flat_map(|clause| match &clause.matcher {
+                Matcher::Term(term) => vec![term.clone()],
+                Matcher::Phrase(tokens) => tokens.clone(),
+                Matcher::Exact(_) => Vec::new(),
+            })
+            .collect();
+        terms.sort();
+        terms.dedup();
+        terms
+    }
+
+    /// Terms each group requires, to narrow a search down to the units in their
+    /// postings. None when some group requires no term (e.g. only `category:guide` or
+    /// `-word`), as nothing can be ruled out then.
+    pub fn required_terms(&self) -> Option<Vec<Vec<&str>>> {
+        if self.groups.is_empty() {
+            return None;
+        }
+        self.groups
+            .iter()
+            .map(|group| {
+                let terms: Vec<&str> = group
+                    .iter()
+                    .filter(|clause| !clause.negated)
+                    .flat_map(|clause| match &clause.matcher {
+                        Matcher::Term(term) => vec![term.as_str()],
+                        Matcher::Phrase(tokens) => tokens.iter().map(String::as_str).collect(),
+                        Matcher::Exact(_) => Vec::new(),
+                    })
+                    .collect();
+                (!terms.is_empty()).then_some(terms)
+            })
+            .collect()
+    }
+
+    /// Whether the target satisfies the query. An empty query matches everything.
+    pub fn matches(&self, target: &Target, index: &SearchIndex) -> bool {
+        self.matches_within(target, target, index)
+    }
+
+    /// Whether a part of a document, such as a section, satisfies the query. Positive
+    /// clauses are matched against the part and negated ones against the whole
+    /// document, so an exclusion rules out every part of a document it matches.
+    pub fn matches_within(&self, part: &Target, document: &Target, index: &SearchIndex) -> bool {
+        self.groups.is_empty()
+            || self.groups.iter().any(|group| {
+                group.iter().all(|clause| {
+                    if clause.negated {
+                        !clause.matches(document, index)
+                    } else {
+                        clause.matches(part, index)
+                    }
+                })
+            })
+    }
+}
+
+impl Clause {
+    fn matches(&self, target: &Target, index: &SearchIndex) -> bool {
+        match (&self.matcher, self.field) {
+            (Matcher::Exact(value), Field::Category) => target.category.to_lowercase() == *value,
+            (Matcher::Exact(value), Field::Repo) => target.repo.to_lowercase() == *value,
+            (Matcher::Term(term), Field::Title) => target.in_title(index, term),
+            (Matcher::Term(term), _) => target.in_document(index, term),
+            (Matcher::Phrase(tokens), Field::Title) => {
+                tokens.iter().all(|t| target.in_title(index, t))
+                    && contains_phrase(target.title_tokens(), tokens)
+            }
+            (Matcher::Phrase(tokens), _) => {
+                tokens.iter().all(|t| target.in_document(index, t))
+                    && (contains_phrase(target.title_tokens(), tokens)
+                        || contains_phrase(target.content_tokens(), tokens))
+            }
+            (Matcher::Exact(_), _) => false,
+        }
+    }
+}
+
+fn read_quoted(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> String {
+    let mut value = String::new();
+    for c in chars.by_ref() {
+        if c == '"' {
+            break;
+        }
+        value.push(c);
+    }
+    value
+}
+
+fn contains_phrase(tokens: &[String], phrase: &[String]) -> bool {
+    tokens.windows(phrase.len()).any(|window| window == phrase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(field: Field, term: &str, negated: bool) -> (Field, Matcher, bool) {
+        (field, Matcher::Term(term.to_string()), negated)
+    }
+
+    fn clauses(query: &Query) -> Vec<Vec<(Field, Matcher, bool)>> {
+        query
+            .groups
+            .iter()
+            .map(|group| {
+                group
+                    .iter()
+                    .map(|c| (c.field, c.matcher.clone(), c.negated))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_terms_phrases_and_negation() {
+        let query = Query::parse(r#"Deploy "Helm Chart" -draft -"old api""#);
+        assert_eq!(
+            clauses(&query),
+            [[
+                term(Field::Any, "deploy", false),
+                (Field::Any, Matcher::Phrase(vec!["helm".into(), "chart".into()]), false),
+                term(Field::Any, "draft", true),
+                (Field::Any, Matcher::Phrase(vec!["old".into(), "api".into()]), true),
+            ]]
+        );
+    }
+
+    #[test]
+    fn parses_field_prefixes() {
+        let query = Query::parse(r#"title:"Quick Start" title:install category:Guide repo:Dynamo"#);
+        assert_eq!(
+            clauses(&query),
+            [[
+                (Field::Title, Matcher::Phrase(vec!["quick".into(), "start".into()]), false),
+                term(Field::Title, "install", false),
+                (Field::Category, Matcher::Exact("guide".into()), false),
+                (Field::Repo, Matcher::Exact("dynamo".into()), false),
+            ]]
+        );
+
+        // Unknown prefixes are searched for as text
+        let query = Query::parse("http://example");
+        assert_eq!(
+            clauses(&query),
+            [[(Field::Any, Matcher::Phrase(vec!["http".into(), "example".into()]), false)]]
+        );
+    }
+
+    #[test]
+    fn splits_groups_on_or_and_ignores_and() {
+        let query = Query::parse("kv AND cache OR router OR");
+        assert_eq!(
+            clauses(&query),
+            [
+                vec![term(Field::Any, "kv", false), term(Field::Any, "cache", false)],
+                vec![term(Field::Any, "router", false)],
+            ]
+        );
+
+        // Only the bare uppercase keywords are operators
+        let query = Query::parse(r#"or "OR" -OR"#);
+        assert_eq!(
+            clauses(&query),
+            [[
+                term(Field::Any, "or", false),
+                term(Field::Any, "or", false),
+                term(Field::Any, "or", true),
+            ]]
+        );
+    }
+
+    #[test]
+    fn skips_clauses_without_tokens() {
+        assert!(Query::parse("  -- \"\" category: OR ").groups.is_empty());
+    }
+
+    #[test]
+    fn collects_scoring_content_and_required_terms() {
+        let query = Query::parse(r#"title:setup "kv cache" -slow OR category:guide"#);
+        assert_eq!(query.scoring_terms(), ["cache", "kv", "setup"]);
+        assert_eq!(query.content_terms(), ["cache", "kv"]);
+        // The second group requires no term, so no document can be ruled out
+        assert_eq!(query.required_terms(), None);
+
+        let query = Query::parse(r#"title:setup "kv cache" -slow OR router"#);
+        assert_eq!(
+            query.required_terms(),
+            Some(vec![vec!["setup", "kv", "cache"], vec!["router"]])
+        );
+        assert_eq!(Query::parse("").required_terms(), None);
+    }
+
+    #[test]
+    fn matches_against_the_index_and_target_fields() {
+        let mut index = SearchIndex::new();
+        let (title, content) = ("KV Cache Routing", "The router picks a worker by cache overlap");
+        index.insert("doc", title, content);
+        let keys = ["doc".to_string()];
+        let target = Target::new(&keys, title, content, "guide", "dynamo");
+        let matches = |query: &str| Query::parse(query).matches(&target, &index);
+
+        assert!(matches(""));
+        assert!(matches("router worker"));
+        assert!(!matches("router missing"));
+        assert!(matches("missing OR router"));
+        assert!(matches("title:routing"));
+        assert!(!matches("title:router"));
+        assert!(matches(r#""picks a worker""#));
+        assert!(!matches(r#""worker picks""#));
+        assert!(matches(r#"title:"kv cache""#));
+        assert!(!matches(r#"title:"cache overlap""#));
+        assert!(matches("category:GUIDE repo:dynamo"));
+        assert!(!matches("repo:other"));
+        assert!(!matches("router -overlap"));
+    }
+
+    #[test]
+    fn matches_terms_across_all_keys_of_a_target() {
+        let mut index = SearchIndex::new();
+        index.insert("doc#intro", "Intro", "install the package");
+        index.insert("doc#usage", "Usage", "run the server");
+        let keys = ["doc#intro".to_string(), "doc#usage".to_string()];
+        let target = Target::new(&keys, "", "", "", "");
+
+        assert!(Query::parse("install server").matches(&target, &index));
+        assert!(Query::parse("title:usage install").matches(&target, &index));
+        assert!(!Query::parse("install -server").matches(&target, &index));
+    }
+}
@@ -0,0 +1,206 @@
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tracing::{info, warn};
+
+use crate::repo_manager::{RefreshReport, RepoManager};
+use crate::transport;
+use crate::DocumentIndex;
+
+/// Held for a whole refresh, so the background ticker and the `refresh_repos` tool
+/// don't fetch and fast-forward the same worktrees at once
+static REFRESHING: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Fetch every repository, fast-forward the worktrees that are on a branch, and
+/// re-index the documents that changed, notifying the client if any did. Returns what
+/// changed in each repository.
+pub async fn refresh_repos(
+    index: &RwLock<DocumentIndex>,
+    repo_manager: &RwLock<RepoManager>,
+) -> Result<Vec<RefreshReport>> {
+    let _refreshing = REFRESHING.lock().await;
+
+    // Fetch from a snapshot, so switching versions or adding repositories meanwhile
+    // doesn't wait for every fetch (and block later readers behind it)
+    let snapshot = repo_manager.read().await.clone();
+    let mut reports = snapshot.refresh().await?;
+
+    let mut changed = 0;
+    for report in &mut reports {
+        let Some(update) = &report.fast_forward else {
+            continue;
+        };
+        let name = &report.repo;
+
+        info!(
+            "Fast-forwarded {} on {} from {} to {} ({} files changed)",
+            name,
+            update.version,
+            update.from,
+            update.to,
+            update.changed.len()
+        );
+
+        {
+            let mut index = index.write().await;
+            // The repository was switched to another version in the meantime. Its
+            // worktree has moved all the same, so drop the stale documents for it to be
+            // indexed afresh when switched back to.
+            if index.active_version(name) != Some(update.version.as_str()) {
+                if index.is_loaded(name, &update.version) {
+                    index.remove_version(name, &update.version);
+                }
+                continue;
+            }
+            for path in &update.changed {
+                match index.update_path(name, &update.path, &update.path.join(path)) {
+                    Ok(true) => report.reindexed += 1,
+                    Ok(false) => {}
+                    Err(e) => warn!("Failed to re-index {} in {}: {}", path, name, e),
+                }
+            }
+            changed += report.reindexed;
+        }
+
+        // Persist the new commit's index holding only a read lock, and write it out on a
+        // blocking thread without any lock
+        let persisted = {
+            let index = index.read().await;
+            index
+                .loader(name)
+                .filter(|_| index.is_loaded(name, &update.version))
+                .map(|loader| {
+                    let snapshot =
+                        index.snapshot(name, &update.version, &update.path, &loader.rules);
+                    (loader, snapshot)
+                })
+        };
+        if let Some((loader, snapshot)) = persisted {
+            let commit = update.to.clone();
+            if let Err(e) =
+                tokio::task::spawn_blocking(move || loader.persist(&commit, &snapshot)).await
+            {
+                warn!("Failed to persist index for {}: {}", name, e);
+            }
+        }
+    }
+
+    if changed > 0 {
+        info!("Re-indexed {} changed documents", changed);
+        transport::notify("notifications/resources/list_changed");
+    }
+    Ok(reports)
+}
+
+/// Refresh repositories every `period` in the background, starting one period from
+/// now since repositories were just fetched at startup.
+pub fn spawn_periodic(
+    period: Duration,
+    index: Arc<RwLock<DocumentIndex>>,
+    repo_manager: Arc<RwLock<RepoManager>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(period);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // The first tick completes immediately
+        ticks.tick().await;
+        loop {
+            ticks.tick().await;
+            // Failed fetches are logged as they happen
+            if let Err(e) = refresh_repos(&index, &repo_manager).await {
+                warn!("Background refresh failed: {}", e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index_cache::IndexCache;
+    use crate::rules::IndexRules;
+    use crate::test_support::{git, remote_repo, TempDir};
+    use std::collections::{BTreeMap, HashMap};
+    use std::path::PathBuf;
+
+    /// Repository `r` set up on `main` from a fixture remote, with its docs indexed
+    async fn setup(dir: &TempDir) -> (PathBuf, RwLock<DocumentIndex>, RwLock<RepoManager>) {
+        let (remote, forge) = remote_repo(dir.path());
+        let mut manager = RepoManager::with_cache_dir(dir.path().join("cache"), false).unwrap();
+        manager
+            .setup_repo("r", "o", "r", &forge, Some("main"), false)
+            .await
+            .unwrap();
+        let rules = IndexRules::new(&["**/*.md".to_string()], &[], &BTreeMap::new()).unwrap();
+        let cache = IndexCache::new(manager.index_cache_dir());
+        let mut index = DocumentIndex::new(HashMap::from([("r".to_string(), rules)]), cache);
+        index.index_from_manager(&manager).await.unwrap();
+        (remote, RwLock::new(index), RwLock::new(manager))
+    }
+
+    #[tokio::test]
+    async fn fast_forwards_branches_and_reindexes_changed_documents() {
+        let dir = TempDir::new("refresh-fast-forward");
+        let (remote, index, repo_manager) = setup(&dir).await;
+        assert!(index.read().await.get("r/README", None).is_some());
+
+        std::fs::write(remote.join("docs/guide.md"), "# Guide\nUpgrade it\n").unwrap();
+        std::fs::write(remote.join("docs/new.md"), "# New\nFresh page\n").unwrap();
+        git(&remote, &["rm", "-q", "README.md"]);
+        git(&remote, &["add", "."]);
+        git(&remote, &["commit", "-q", "-m", "Update docs"]);
+        let head = git(&remote, &["rev-parse", "HEAD"]);
+
+        let reports = refresh_repos(&index, &repo_manager).await.unwrap();
+        let report = &reports[0];
+        assert_eq!(report.error, None);
+        assert_eq!(report.updated_branches[0].new, head);
+        assert_eq!(report.reindexed, 3);
+        assert!(!report.behind);
+
+        let worktree = repo_manager.read().await.get_path("r").unwrap();
+        assert_eq!(git(&worktree, &["rev-parse", "HEAD"]), head);
+        let index = index.read().await;
+        assert_eq!(
+            index.get("r/docs/guide", None).unwrap().content,
+            "# Guide\nUpgrade it\n"
+        );
+        assert!(index.get("r/docs/new", None).is_some());
+        assert!(index.get("r/README", None).is_none());
+
+        // The new commit's index is persisted for the next startup
+        let rules = IndexRules::new(&["**/*.md".to_string()], &[], &BTreeMap::new()).unwrap();
+        let snapshot = index.cache.load("r", &head, &rules.fingerprint()).unwrap();
+        let mut paths: Vec<&str> = snapshot.documents.iter().map(|d| d.path.as_str()).collect();
+        paths.sort();
+        assert_eq!(paths, ["docs/guide.md", "docs/new.md"]);
+    }
+
+    #[tokio::test]
+    async fn reports_worktrees_that_cannot_be_fast_forwarded_as_behind() {
+        let dir = TempDir::new("refresh-diverged");
+        let (remote, index, repo_manager) = setup(&dir).await;
+        let worktree = repo_manager.read().await.get_path("r").unwrap();
+        git(
+            &worktree,
+            &["commit", "-q", "--allow-empty", "-m", "Local change"],
+        );
+        git(
+            &remote,
+            &["commit", "-q", "--allow-empty", "-m", "Remote change"],
+        );
+        let head = git(&remote, &["rev-parse", "HEAD"]);
+
+        let report = &refresh_repos(&index, &repo_manager).await.unwrap()[0];
+        assert!(report.fetched);
+        assert!(report.error.is_some());
+        assert!(report.behind);
+        assert_eq!(report.latest.as_deref(), Some(head.as_str()));
+        assert_eq!(report.reindexed, 0);
+        assert!(index.read().await.get("r/README", None).is_some());
+    }
+}
@@ -1,10 +1,20 @@
 use anyhow::{Context, Result};
-use std::collections::HashMap;
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::process::Command;
 use tracing::{info, warn};
 
+use crate::changelog::{self, Changelog};
+use crate::forge::{self, Forge, ForgeConfig, ForgeKind, Release, RestClient};
+use crate::git_forge::GitForge;
+use crate::gitea::GiteaClient;
 use crate::github::GitHubClient;
+use crate::gitlab::GitLabClient;
+use crate::http_cache::HttpCache;
+use crate::versions::{self, VersionSpec};
 
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct VersionInfo {
@@ -12,33 +22,341 @@ pub struct VersionInfo {
     pub current_version: String,
     pub current_branch: Option<String>,
     pub current_commit: Option<String>,
-    pub branches: Vec<String>,
-    pub tags: Vec<String>,
-    pub releases: Vec<String>,
+    /// Most recently committed first
+    pub branches: VersionPage<BranchInfo>,
+    /// Highest semantic version first, then tags that aren't versions by name
+    pub tags: VersionPage<TagInfo>,
+    /// Most recently published first
+    pub releases: VersionPage<Release>,
+    /// Why releases couldn't be listed, e.g. the API rate limit was exceeded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub releases_error: Option<String>,
 }
 
+/// One page of a sorted version list
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct VersionPage<T> {
+    /// Number of entries matching the filter, across all pages
+    pub total: usize,
+    pub offset: usize,
+    pub items: Vec<T>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BranchInfo {
+    pub name: String,
+    pub commit: String,
+    pub last_commit_date: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TagInfo {
+    pub name: String,
+    pub commit: String,
+    pub date: String,
+    pub prerelease: bool,
+}
+
+/// Filtering and pagination for `list_versions`, applied to each list separately
+#[derive(Debug, Clone)]
+pub struct VersionFilter {
+    /// Case-insensitive substring of the branch, tag or release name
+    pub name: Option<String>,
+    pub include_prereleases: bool,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl Default for VersionFilter {
+    fn default() -> Self {
+        Self {
+            name: None,
+            include_prereleases: true,
+            offset: 0,
+            limit: 20,
+        }
+    }
+}
+
+impl VersionFilter {
+    fn matches_name(&self, names: &[&str]) -> bool {
+        match &self.name {
+            Some(filter) => {
+                let filter = filter.to_lowercase();
+                names.iter().any(|name| name.to_lowercase().contains(&filter))
+            }
+            None => true,
+        }
+    }
+    
+    fn page<T>(&self, items: Vec<T>) -> VersionPage<T> {
+        VersionPage {
+            total: items.len(),
+            offset: self.offset,
+            items: items.into_iter().skip(self.offset).take(self.limit).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RemovedWorktree {
+    pub path: String,
+    pub last_used: String,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CleanupReport {
+    pub removed: Vec<RemovedWorktree>,
+    pub freed_bytes: u64,
+    /// Inactive worktrees left in place
+    pub kept: usize,
+    /// Size of the worktree cache after cleanup, including active worktrees
+    pub total_bytes: u64,
+    /// Whether the cache is still above the disk budget
+    pub over_budget: bool,
+    /// Worktrees that couldn't be removed and bookkeeping that couldn't be updated
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+/// Documentation files that differ between two versions of a repository
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DocsDiff {
+    pub repo: String,
+    /// Refs the requested versions resolved to
+    pub from: String,
+    pub to: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+    pub renamed: Vec<RenamedFile>,
+    /// Unified diff of the requested file, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RenamedFile {
+    pub from: String,
+    pub to: String,
+    /// Whether the content changed as well
+    pub modified: bool,
+}
+
+/// What a refresh changed in one repository
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RefreshReport {
+    pub repo: String,
+    pub fetched: bool,
+    /// Why the repository wasn't fetched, or its worktree couldn't be fast-forwarded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Highest semantic version first
+    pub new_tags: Vec<String>,
+    /// Releases not seen by the previous refresh, in the forge's order
+    pub new_releases: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub releases_error: Option<String>,
+    pub updated_branches: Vec<BranchUpdate>,
+    pub active_version: String,
+    /// Whether the worktree's branch has fetched commits that aren't checked out, or
+    /// a newer stable release than the checked out one exists
+    pub behind: bool,
+    /// Commit or release the active version is behind
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest: Option<String>,
+    /// Documents re-indexed after fast-forwarding the active branch
+    pub reindexed: usize,
+    #[serde(skip)]
+    pub fast_forward: Option<FastForward>,
+}
+
+impl RefreshReport {
+    fn new(name: &str, repo_info: &RepoInfo) -> Self {
+        RefreshReport {
+            repo: name.to_string(),
+            fetched: false,
+            error: None,
+            new_tags: Vec::new(),
+            new_releases: Vec::new(),
+            releases_error: None,
+            updated_branches: Vec::new(),
+            active_version: repo_info.current_version.clone(),
+            behind: false,
+            latest: None,
+            reindexed: 0,
+            fast_forward: None,
+        }
+    }
+}
+
+/// A branch that moved or appeared on the remote
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BranchUpdate {
+    pub name: String,
+    /// None for new branches
+    pub old: Option<String>,
+    pub new: String,
+}
+
+/// A worktree fast-forwarded to the fetched head of the branch it is on
+#[derive(Debug, Clone)]
+pub struct FastForward {
+    /// Version the repository is at, i.e. the branch name
+    pub version: String,
+    pub path: PathBuf,
+    pub from: String,
+    pub to: String,
+    /// Files added, modified or deleted, relative to the repository root
+    pub changed: Vec<String>,
+}
+
+/// A worktree checked out at a resolved version
+#[derive(Debug, Clone)]
+pub struct Checkout {
+    pub path: PathBuf,
+    /// Branch, tag or commit the requested version resolved to
+    pub version: String,
+    pub commit: Option<String>,
+    /// Keeps cleanup away from the worktree until it is indexed or made current
+    lease: WorktreeLease,
+}
+
+/// Serializes updates of the JSON record files under the cache directory, so concurrent
+/// updates don't lose each other's entries
+static RECORD_LOCK: Mutex<()> = Mutex::new(());
+
+/// Worktrees in use and worktrees being removed, shared by every `RepoManager` so
+/// cleanup never removes a worktree that is being read
+static WORKTREE_USE: Lazy<Mutex<WorktreeUse>> = Lazy::new(Default::default);
+
+#[derive(Default)]
+struct WorktreeUse {
+    /// Number of leases per worktree
+    held: HashMap<PathBuf, usize>,
+    removing: HashSet<PathBuf>,
+}
+
+/// Keeps a worktree from being removed by `cleanup_old_worktrees` while it is checked
+/// out, indexed, loaded or current. Clones are further leases on the same worktree.
+#[derive(Debug)]
+pub struct WorktreeLease(PathBuf);
+
+impl WorktreeLease {
+    /// Lease a worktree, waiting while cleanup is removing it
+    async fn acquire(path: &Path) -> Self {
+        loop {
+            {
+                let mut worktrees = WORKTREE_USE.lock().unwrap();
+                if !worktrees.removing.contains(path) {
+                    *worktrees.held.entry(path.to_path_buf()).or_default() += 1;
+                    return Self(path.to_path_buf());
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+    
+    /// Lease a worktree the caller already holds a lease on, or one cleanup doesn't
+    /// manage such as a local checkout
+    pub fn hold(path: &Path) -> Self {
+        *WORKTREE_USE.lock().unwrap().held.entry(path.to_path_buf()).or_default() += 1;
+        Self(path.to_path_buf())
+    }
+}
+
+impl Clone for WorktreeLease {
+    fn clone(&self) -> Self {
+        Self::hold(&self.0)
+    }
+}
+
+impl Drop for WorktreeLease {
+    fn drop(&mut self) {
+        let mut worktrees = WORKTREE_USE.lock().unwrap();
+        if let Some(count) = worktrees.held.get_mut(&self.0) {
+            *count -= 1;
+            if *count == 0 {
+                worktrees.held.remove(&self.0);
+            }
+        }
+    }
+}
+
+/// Marks a worktree as being removed, so it can't be leased meanwhile
+struct Removal(PathBuf);
+
+impl Removal {
+    /// None if the worktree is leased or already being removed
+    fn start(path: &Path) -> Option<Self> {
+        let mut worktrees = WORKTREE_USE.lock().unwrap();
+        if worktrees.held.contains_key(path) || !worktrees.removing.insert(path.to_path_buf()) {
+            return None;
+        }
+        Some(Self(path.to_path_buf()))
+    }
+}
+
+impl Drop for Removal {
+    fn drop(&mut self) {
+        WORKTREE_USE.lock().unwrap().removing.remove(&self.0);
+    }
+}
+
+/// Cloning is cheap and snapshots the managed repositories, for long-running work that
+/// shouldn't hold a lock on the manager
+#[derive(Clone)]
 pub struct RepoManager {
     cache_base: PathBuf,
     bare_repos: PathBuf,
     worktrees: PathBuf,
     repos: HashMap<String, RepoInfo>,
+    /// Never touch the network: only cached bare repositories and releases are used
+    offline: bool,
+    /// Shared by snapshots
+    resolved: Arc<Mutex<ResolvedVersions>>,
 }
 
+/// What version specs such as `latest` or `~0.4` resolved to and when, keyed by bare
+/// clone and spec
+type ResolvedVersions = HashMap<(PathBuf, String), (String, Instant)>;
+
 #[derive(Debug, Clone)]
 struct RepoInfo {
     owner: String,
     name: String,
+    forge: ForgeConfig,
     current_version: String,
     worktree_path: PathBuf,
+    /// Keeps cleanup away from the current worktree
+    lease: WorktreeLease,
+}
+
+/// A repository cloned and checked out by `prepare_repo`, not yet managed
+#[derive(Debug, Clone)]
+pub struct PreparedRepo {
+    name: String,
+    info: RepoInfo,
+}
+
+impl PreparedRepo {
+    pub fn version(&self) -> &str {
+        &self.info.current_version
+    }
 }
 
 impl RepoManager {
-    pub fn new() -> Result<Self> {
+    pub fn new(offline: bool) -> Result<Self> {
         let cache_base = dirs::home_dir()
             .ok_or_else(|| anyhow::anyhow!("No home directory"))?
             .join(".cache")
             .join("dynamo-mcp");
-        
+        Self::with_cache_dir(cache_base, offline)
+    }
+    
+    /// Manage repositories cached under `cache_base` instead of `~/.cache/dynamo-mcp`
+    pub fn with_cache_dir(cache_base: PathBuf, offline: bool) -> Result<Self> {
         let bare_repos = cache_base.join("bare");
         let worktrees = cache_base.join("worktrees");
         
@@ -51,18 +369,36 @@ impl RepoManager {
             bare_repos,
             worktrees,
             repos: HashMap::new(),
+            offline,
+            resolved: Arc::new(Mutex::new(HashMap::new())),
         })
     }
     
     /// Setup a repository with optional version override
-    pub fn setup_repo(
+    pub async fn setup_repo(
         &mut self,
         name: &str,
         owner: &str,
         repo: &str,
+        forge: &ForgeConfig,
         version: Option<&str>,
         use_local: bool,
     ) -> Result<PathBuf> {
+        let prepared = self.prepare_repo(name, owner, repo, forge, version, use_local).await?;
+        Ok(self.insert_repo(prepared))
+    }
+    
+    /// Clone or fetch a repository and check out its worktree without managing it yet,
+    /// so the slow part of setting it up only needs shared access
+    pub async fn prepare_repo(
+        &self,
+        name: &str,
+        owner: &str,
+        repo: &str,
+        forge: &ForgeConfig,
+        version: Option<&str>,
+        use_local: bool,
+    ) -> Result<PreparedRepo> {
         // Check for local override
         if use_local {
             let local_path = dirs::home_dir()
@@ -72,135 +408,709 @@ impl RepoManager {
             
             if local_path.exists() {
                 info!("Using local repository at {:?}", local_path);
-                self.repos.insert(name.to_string(), RepoInfo {
-                    owner: owner.to_string(),
-                    name: repo.to_string(),
-                    current_version: "local".to_string(),
-                    worktree_path: local_path.clone(),
+                return Ok(PreparedRepo {
+                    name: name.to_string(),
+                    info: RepoInfo {
+                        owner: owner.to_string(),
+                        name: repo.to_string(),
+                        forge: forge.clone(),
+                        current_version: "local".to_string(),
+                        lease: WorktreeLease::hold(&local_path),
+                        worktree_path: local_path,
+                    },
                 });
-                return Ok(local_path);
             }
         }
         
         // Setup bare repository if needed
-        let bare_path = self.bare_repos.join(format!("{}.git", repo));
+        let bare_path = self.bare_path(name);
+        let client = self.forge(name, owner, repo, forge);
         if !bare_path.exists() {
-            self.clone_bare_repo(owner, repo, &bare_path)?;
+            if self.offline {
+                anyhow::bail!("{}/{} is not cached and can't be cloned in offline mode", owner, repo);
+            }
+            self.clone_bare_repo(&client.clone_url(), &bare_path).await?;
         }
         
         // Fetch latest changes
-        self.fetch_updates(&bare_path)?;
+        if !self.offline {
+            self.update_origin(&bare_path, &client.clone_url()).await?;
+            if let Err(e) = self.fetch_updates(&bare_path).await {
+                warn!("{}", e);
+            }
+        }
         
         // Determine version to use
         let version = if let Some(v) = version {
-            v.to_string()
+            self.resolve_version(client.as_ref(), repo, &bare_path, v).await?
+        } else if self.offline {
+            self.cached_latest_version(owner, repo, &bare_path).await
         } else {
-            // Default to latest release, fallback to main
-            let github = GitHubClient::new(owner, repo);
-            github.get_latest_release()?
-                .unwrap_or_else(|| {
-                    github.get_default_branch()
-                        .unwrap_or_else(|_| "main".to_string())
-                })
+            // Default to latest release, fallback to the default branch
+            match client.latest_release().await {
+                Ok(Some(release)) => {
+                    self.record_latest_release(owner, repo, &release);
+                    release
+                },
+                Ok(None) => match client.default_branch().await {
+                    Ok(branch) => branch,
+                    Err(e) => {
+                        warn!("Failed to get default branch of {}/{}: {}", owner, repo, e);
+                        self.bare_default_branch(&bare_path).await
+                    },
+                },
+                Err(e) => {
+                    warn!("Failed to get latest release of {}/{}: {}", owner, repo, e);
+                    self.cached_latest_version(owner, repo, &bare_path).await
+                },
+            }
         };
         
         // Create or reuse worktree
-        let worktree_path = self.create_worktree(repo, &bare_path, &version)?;
-        
-        // Store repo info
-        self.repos.insert(name.to_string(), RepoInfo {
-            owner: owner.to_string(),
-            name: repo.to_string(),
-            current_version: version.clone(),
-            worktree_path: worktree_path.clone(),
-        });
+        let (worktree_path, lease) = self.create_worktree(name, &bare_path, &version).await?;
         
         info!("Setup {} at version {} in {:?}", name, version, worktree_path);
-        Ok(worktree_path)
+        Ok(PreparedRepo {
+            name: name.to_string(),
+            info: RepoInfo {
+                owner: owner.to_string(),
+                name: repo.to_string(),
+                forge: forge.clone(),
+                current_version: version,
+                worktree_path,
+                lease,
+            },
+        })
     }
     
-    /// Switch a repository to a different version
-    pub fn switch_version(&mut self, name: &str, version: &str) -> Result<PathBuf> {
+    /// Start managing a repository set up by `prepare_repo`. Returns its worktree path.
+    pub fn insert_repo(&mut self, prepared: PreparedRepo) -> PathBuf {
+        let path = prepared.info.worktree_path.clone();
+        self.repos.insert(prepared.name, prepared.info);
+        path
+    }
+    
+    /// Make a checkout from `checkout_version` the current version of a repository
+    pub fn switch_version(&mut self, name: &str, checkout: &Checkout) -> Result<()> {
+        let repo_info = self.repos.get_mut(name)
+            .ok_or_else(|| anyhow::anyhow!("Repository {} not setup", name))?;
+        repo_info.current_version = checkout.version.clone();
+        repo_info.worktree_path = checkout.path.clone();
+        repo_info.lease = checkout.lease.clone();
+        
+        info!("Switched {} to version {}", name, checkout.version);
+        Ok(())
+    }
+    
+    /// Check out a version of a repository in its own worktree, without making it the
+    /// current version
+    pub async fn checkout_version(&self, name: &str, version: &str) -> Result<Checkout> {
         let repo_info = self.repos.get(name)
-            .ok_or_else(|| anyhow::anyhow!("Repository {} not setup", name))?
-            .clone();
+            .ok_or_else(|| anyhow::anyhow!("Repository {} not setup", name))?;
         
-        let bare_path = self.bare_repos.join(format!("{}.git", repo_info.name));
+        let bare_path = self.bare_path(name);
+        if !bare_path.exists() {
+            anyhow::bail!("Repository {} is served from a local checkout and has no other versions", name);
+        }
+        let version = self.resolve_version(self.repo_forge(name, repo_info).as_ref(), &repo_info.name, &bare_path, version).await?;
         
         // Create new worktree for this version
-        let worktree_path = self.create_worktree(&repo_info.name, &bare_path, version)?;
-        
-        // Update repo info
-        self.repos.insert(name.to_string(), RepoInfo {
-            owner: repo_info.owner,
-            name: repo_info.name,
-            current_version: version.to_string(),
-            worktree_path: worktree_path.clone(),
-        });
-        
-        info!("Switched {} to version {}", name, version);
-        Ok(worktree_path)
+        let (path, lease) = self.create_worktree(name, &bare_path, &version).await?;
+        let commit = self.get_current_commit(&path).await?;
+        Ok(Checkout { path, version, commit, lease })
+    }
+    
+    /// Stop managing a repository. Its bare clone and worktrees stay on disk; the
+    /// worktrees become inactive and can be reclaimed by `cleanup_old_worktrees`.
+    pub fn remove_repo(&mut self, name: &str) -> Option<PathBuf> {
+        let repo_info = self.repos.remove(name)?;
+        info!("Removed {} (was at {:?})", name, repo_info.worktree_path);
+        Some(repo_info.worktree_path)
     }
     
-    /// Refresh repositories by fetching latest changes
-    pub fn refresh(&mut self) -> Result<()> {
-        for repo_info in self.repos.values() {
-            let bare_path = self.bare_repos.join(format!("{}.git", repo_info.name));
+    /// Fetch every repository and fast-forward the worktrees that are on a branch,
+    /// reporting what changed. A failed fetch is reported without stopping the others.
+    pub async fn refresh(&self) -> Result<Vec<RefreshReport>> {
+        if self.offline {
+            anyhow::bail!("Refreshing is disabled in offline mode");
+        }
+        let mut reports = Vec::new();
+        for name in self.names() {
+            let repo_info = &self.repos[&name];
+            let bare_path = self.bare_path(&name);
             if bare_path.exists() {
-                self.fetch_updates(&bare_path)?;
-                info!("Refreshed {}", repo_info.name);
+                reports.push(self.refresh_repo(&name, repo_info, &bare_path).await);
+            } else {
+                let mut report = RefreshReport::new(&name, repo_info);
+                report.error = Some(if repo_info.current_version == "local" {
+                    "Served from a local checkout, which isn't fetched".to_string()
+                } else {
+                    "No bare clone to fetch into".to_string()
+                });
+                reports.push(report);
             }
         }
-        Ok(())
+        Ok(reports)
     }
     
-    /// List available versions for a repository
-    pub fn list_versions(&self, name: &str) -> Result<VersionInfo> {
+    async fn refresh_repo(&self, name: &str, repo_info: &RepoInfo, bare_path: &Path) -> RefreshReport {
+        let mut report = RefreshReport::new(name, repo_info);
+        
+        let branches_before: HashMap<String, String> = self.get_branches(bare_path).await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|b| (b.name, b.commit))
+            .collect();
+        let tags_before: HashSet<String> = self.get_tags(bare_path).await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        
+        if let Err(e) = self.fetch_updates(bare_path).await {
+            warn!("Failed to refresh {}: {}", name, e);
+            report.error = Some(e.to_string());
+            return report;
+        }
+        report.fetched = true;
+        info!("Refreshed {}", name);
+        // New tags may match version specs differently now
+        self.resolved.lock().unwrap().retain(|(path, _), _| path != bare_path);
+        
+        let mut tags: Vec<String> = self.get_tags(bare_path).await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        report.new_tags = tags.iter()
+            .filter(|t| !tags_before.contains(*t))
+            .cloned()
+            .collect();
+        report.new_tags.sort_by(|a, b| versions::compare_tags(a, b));
+        
+        let mut branches = self.get_branches(bare_path).await.unwrap_or_default();
+        branches.sort_by(|a, b| a.name.cmp(&b.name));
+        for branch in branches {
+            let old = branches_before.get(&branch.name);
+            if old != Some(&branch.commit) {
+                report.updated_branches.push(BranchUpdate {
+                    name: branch.name,
+                    old: old.cloned(),
+                    new: branch.commit,
+                });
+            }
+        }
+        
+        // Releases are new if the previous refresh didn't list them. Until one has,
+        // those whose tag wasn't fetched before are.
+        match self.repo_forge(name, repo_info).list_releases().await {
+            Ok(releases) => {
+                let known = self.load_known_releases().remove(name);
+                let release_tags: Vec<String> = releases.into_iter().map(|r| r.tag_name).collect();
+                for tag in &release_tags {
+                    let seen = match &known {
+                        Some(known) => known.contains(tag),
+                        None => tags_before.contains(tag),
+                    };
+                    if !seen {
+                        report.new_releases.push(tag.clone());
+                    }
+                    if !tags.contains(tag) {
+                        tags.push(tag.clone());
+                    }
+                }
+                self.record_known_releases(name, release_tags);
+            },
+            Err(e) => report.releases_error = Some(e.to_string()),
+        }
+        
+        match self.fast_forward(name).await {
+            Ok(fast_forward) => report.fast_forward = fast_forward,
+            Err(e) => {
+                warn!("Failed to update {}: {}", name, e);
+                report.error = Some(e.to_string());
+            },
+        }
+        match self.newer_version(repo_info, &tags).await {
+            Ok(latest) => {
+                report.behind = latest.is_some();
+                report.latest = latest;
+            },
+            Err(e) => warn!("Failed to check whether {} is up to date: {}", name, e),
+        }
+        report
+    }
+    
+    /// What a repository's active version is behind: the fetched head of the branch its
+    /// worktree is on, or for a release, the highest stable release. None if it is up
+    /// to date, a commit or a local checkout.
+    async fn newer_version(&self, repo_info: &RepoInfo, tags: &[String]) -> Result<Option<String>> {
+        if repo_info.current_version == "local" {
+            return Ok(None);
+        }
+        let path = &repo_info.worktree_path;
+        if let Some(branch) = self.get_current_branch(path).await? {
+            let Some(upstream) = self.rev_parse(path, &format!("refs/remotes/origin/{}", branch)).await? else {
+                return Ok(None);
+            };
+            let output = Command::new("git")
+                .args(["rev-list", "--count", &format!("HEAD..{}", upstream)])
+                .current_dir(path)
+                .output()
+                .await
+                .context("Failed to execute git rev-list")?;
+            let behind: usize = String::from_utf8_lossy(&output.stdout).trim().parse().unwrap_or(0);
+            return Ok((behind > 0).then_some(upstream));
+        }
+        
+        let Some(current) = versions::parse_tag(&repo_info.current_version) else {
+            return Ok(None);
+        };
+        let latest = VersionSpec::Latest.select(tags.iter().map(|t| t.as_str()));
+        Ok(latest
+            .filter(|latest| versions::parse_tag(latest).is_some_and(|v| v > current))
+            .map(|latest| latest.to_string()))
+    }
+    
+    /// Fast-forward a repository's worktree to the fetched head of the branch it is on.
+    /// Returns None for local checkouts, detached worktrees (tags and commits) and
+    /// branches that haven't moved.
+    pub async fn fast_forward(&self, name: &str) -> Result<Option<FastForward>> {
         let repo_info = self.repos.get(name)
             .ok_or_else(|| anyhow::anyhow!("Repository {} not setup", name))?;
+        if repo_info.current_version == "local" {
+            return Ok(None);
+        }
         
-        let bare_path = self.bare_repos.join(format!("{}.git", repo_info.name));
+        let path = &repo_info.worktree_path;
+        let Some((from, to)) = self.fast_forward_worktree(path).await? else {
+            return Ok(None);
+        };
+        
+        // Without renames, a moved file shows up as a deletion and an addition
+        let output = Command::new("git")
+            .args(["diff", "--name-only", "--no-renames", "-z", &from, &to])
+            .current_dir(path)
+            .output()
+            .await
+            .context("Failed to execute git diff")?;
+        let changed = String::from_utf8_lossy(&output.stdout)
+            .split('\0')
+            .filter(|p| !p.is_empty())
+            .map(|p| p.to_string())
+            .collect();
+        
+        Ok(Some(FastForward {
+            version: repo_info.current_version.clone(),
+            path: path.clone(),
+            from,
+            to,
+            changed,
+        }))
+    }
+    
+    /// List available versions for a repository, sorted, filtered and paginated
+    pub async fn list_versions(&self, name: &str, filter: &VersionFilter) -> Result<VersionInfo> {
+        let repo_info = self.repos.get(name)
+            .ok_or_else(|| anyhow::anyhow!("Repository {} not setup", name))?;
+        
+        let bare_path = self.bare_path(name);
         
         // Get branches
-        let branches = self.get_branches(&bare_path)?;
+        let mut branches: Vec<BranchInfo> = self.get_branches(&bare_path).await?
+            .into_iter()
+            .filter(|b| filter.matches_name(&[&b.name]))
+            .collect();
+        branches.sort_by(|a, b| {
+            parse_date(&b.last_commit_date).cmp(&parse_date(&a.last_commit_date))
+                .then_with(|| a.name.cmp(&b.name))
+        });
         
         // Get tags
-        let tags = self.get_tags(&bare_path)?;
+        let mut tags: Vec<TagInfo> = self.get_tags(&bare_path).await?
+            .into_iter()
+            .filter(|t| filter.include_prereleases || !t.prerelease)
+            .filter(|t| filter.matches_name(&[&t.name]))
+            .collect();
+        tags.sort_by(|a, b| versions::compare_tags(&a.name, &b.name));
         
-        // Get GitHub releases
-        let github = GitHubClient::new(&repo_info.owner, &repo_info.name);
-        let releases = github.list_releases().unwrap_or_default();
+        // Get releases from the forge
+        let forge = self.repo_forge(name, repo_info);
+        let (releases, releases_error) = if self.offline && !forge.is_local() {
+            (Vec::new(), Some("offline mode".to_string()))
+        } else {
+            match forge.list_releases().await {
+                Ok(releases) => (releases, None),
+                Err(e) => (Vec::new(), Some(e.to_string())),
+            }
+        };
+        let mut releases: Vec<Release> = releases
+            .into_iter()
+            .filter(|r| filter.include_prereleases || !r.prerelease)
+            .filter(|r| filter.matches_name(&[&r.tag_name, r.name.as_deref().unwrap_or("")]))
+            .collect();
+        releases.sort_by(|a, b| {
+            let published = |r: &Release| r.published_at.as_deref().and_then(parse_date);
+            published(b).cmp(&published(a))
+        });
         
         // Get current commit
-        let current_commit = self.get_current_commit(&repo_info.worktree_path)?;
-        let current_branch = self.get_current_branch(&repo_info.worktree_path)?;
+        let current_commit = self.get_current_commit(&repo_info.worktree_path).await?;
+        let current_branch = self.get_current_branch(&repo_info.worktree_path).await?;
         
         Ok(VersionInfo {
             repo: name.to_string(),
             current_version: repo_info.current_version.clone(),
             current_branch,
             current_commit,
-            branches,
-            tags,
-            releases,
+            branches: filter.page(branches),
+            tags: filter.page(tags),
+            releases: filter.page(releases),
+            releases_error,
         })
     }
     
+    /// Compare the documentation files selected by `is_doc` between two versions, using
+    /// the bare repository so neither version needs a worktree. When `path` is given
+    /// (relative to the repository root), its unified diff is included.
+    pub async fn diff_docs(
+        &self,
+        name: &str,
+        from: &str,
+        to: &str,
+        is_doc: impl Fn(&str) -> bool,
+        path: Option<&str>,
+        context_lines: usize,
+    ) -> Result<DocsDiff> {
+        let repo_info = self.repos.get(name)
+            .ok_or_else(|| anyhow::anyhow!("Repository {} not setup", name))?;
+        
+        let bare_path = self.bare_path(name);
+        if !bare_path.exists() {
+            anyhow::bail!("Repository {} is served from a local checkout and has no other versions", name);
+        }
+        let forge = self.repo_forge(name, repo_info);
+        let from = self.resolve_version(forge.as_ref(), &repo_info.name, &bare_path, from).await?;
+        let to = self.resolve_version(forge.as_ref(), &repo_info.name, &bare_path, to).await?;
+        // Only commit ids are passed on to git diff
+        let from_commit = self.commit_of(&bare_path, &from).await?;
+        let to_commit = self.commit_of(&bare_path, &to).await?;
+        
+        // -z keeps paths unquoted: entries are `status NUL path NUL`, with a second path for renames
+        let output = Command::new("git")
+            .args(["diff", "--name-status", "-M", "-z", &from_commit, &to_commit, "--"])
+            .current_dir(&bare_path)
+            .output()
+            .await
+            .context("Failed to execute git diff")?;
+        
+        if !output.status.success() {
+            anyhow::bail!("Failed to diff {} and {}: {}", from, to,
+                String::from_utf8_lossy(&output.stderr));
+        }
+        
+        let mut diff = DocsDiff {
+            repo: name.to_string(),
+            from: from.clone(),
+            to: to.clone(),
+            added: Vec::new(),
+            removed: Vec::new(),
+            modified: Vec::new(),
+            renamed: Vec::new(),
+            diff: None,
+        };
+        classify_changes(&String::from_utf8_lossy(&output.stdout), is_doc, &mut diff);
+        
+        if let Some(path) = path {
+            // Diff both sides of a rename, so it shows as one change rather than a deletion
+            let mut paths = vec![path];
+            for renamed in &diff.renamed {
+                if renamed.from == path {
+                    paths.push(&renamed.to);
+                } else if renamed.to == path {
+                    paths.push(&renamed.from);
+                }
+            }
+            let output = Command::new("git")
+                .arg("diff")
+                .arg(format!("--unified={}", context_lines))
+                .args(["-M", &from_commit, &to_commit, "--"])
+                .args(&paths)
+                .current_dir(&bare_path)
+                .output()
+                .await
+                .context("Failed to execute git diff")?;
+            
+            if !output.status.success() {
+                anyhow::bail!("Failed to diff {}: {}", path,
+                    String::from_utf8_lossy(&output.stderr));
+            }
+            diff.diff = Some(String::from_utf8_lossy(&output.stdout).to_string());
+        }
+        
+        Ok(diff)
+    }
+    
+    /// Commits reachable from `to` (default: the commit checked out in the worktree)
+    /// but not from `from`, newest first and grouped by conventional commit type. With
+    /// `path`, only commits touching that path prefix are listed, along with the files
+    /// they touched under it. At most `changelog::MAX_COMMITS` commits are listed.
+    pub async fn changelog(
+        &self,
+        name: &str,
+        from: &str,
+        to: Option<&str>,
+        path: Option<&str>,
+        limit: usize,
+    ) -> Result<Changelog> {
+        let repo_info = self.repos.get(name)
+            .ok_or_else(|| anyhow::anyhow!("Repository {} not setup", name))?;
+        let limit = limit.min(changelog::MAX_COMMITS);
+        
+        // Local checkouts carry their own history, which their commits may only exist in
+        let local = repo_info.current_version == "local";
+        let git_path = if local {
+            repo_info.worktree_path.clone()
+        } else {
+            self.bare_path(name)
+        };
+        if !git_path.exists() {
+            anyhow::bail!("Repository {} has no history cache", name);
+        }
+        let forge = self.repo_forge(name, repo_info);
+        let from = self.resolve_version(forge.as_ref(), &repo_info.name, &git_path, from).await?;
+        let from_commit = self.commit_of(&git_path, &from).await?;
+        let (to, to_commit) = match to {
+            Some(to) => {
+                let to = self.resolve_version(forge.as_ref(), &repo_info.name, &git_path, to).await?;
+                let commit = self.commit_of(&git_path, &to).await?;
+                (to, commit)
+            },
+            None => {
+                let commit = self.get_current_commit(&repo_info.worktree_path).await?
+                    .ok_or_else(|| anyhow::anyhow!("Repository {} has no commit checked out", name))?;
+                let label = if local { commit.clone() } else { repo_info.current_version.clone() };
+                (label, commit)
+            },
+        };
+        
+        // Ask for one more commit than the limit to detect truncation
+        let output = Command::new("git")
+            .args(["log", "--no-merges", "--name-only", changelog::LOG_FORMAT])
+            .arg(format!("--max-count={}", limit.saturating_add(1)))
+            .arg(format!("{}..{}", from_commit, to_commit))
+            .arg("--")
+            .args(path)
+            .current_dir(&git_path)
+            .output()
+            .await
+            .context("Failed to execute git log")?;
+        
+        if !output.status.success() {
+            anyhow::bail!("Failed to read history between {} and {}: {}", from, to,
+                String::from_utf8_lossy(&output.stderr));
+        }
+        
+        let mut commits = changelog::parse_log(&String::from_utf8_lossy(&output.stdout));
+        let truncated = commits.len() > limit;
+        commits.truncate(limit);
+        
+        Ok(Changelog {
+            repo: name.to_string(),
+            from,
+            to,
+            path: path.map(|p| p.to_string()),
+            total: commits.len(),
+            truncated,
+            groups: changelog::group_by_type(commits),
+        })
+    }
+    
+    /// Names of the managed repositories
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.repos.keys().cloned().collect();
+        names.sort();
+        names
+    }
+    
     /// Get the current worktree path for a repository
     pub fn get_path(&self, name: &str) -> Option<PathBuf> {
         self.repos.get(name).map(|info| info.worktree_path.clone())
     }
     
+    /// Version a repository's worktree was set up or switched to
+    pub fn current_version(&self, name: &str) -> Option<String> {
+        self.repos.get(name).map(|info| info.current_version.clone())
+    }
+    
+    /// Repositories served from local checkouts (`DYNAMO_USE_LOCAL`), with their paths
+    pub fn local_repos(&self) -> Vec<(String, PathBuf)> {
+        self.repos
+            .iter()
+            .filter(|(_, info)| info.current_version == "local")
+            .map(|(name, info)| (name.clone(), info.worktree_path.clone()))
+            .collect()
+    }
+    
+    /// Commit checked out in a repository's worktree. Returns None for local
+    /// checkouts, whose working tree may have uncommitted changes.
+    pub async fn worktree_commit(&self, name: &str) -> Option<String> {
+        let info = self.repos.get(name)?;
+        if info.current_version == "local" {
+            return None;
+        }
+        self.get_current_commit(&info.worktree_path).await.ok().flatten()
+    }
+    
+    /// Directory holding persisted search indexes
+    pub fn index_cache_dir(&self) -> PathBuf {
+        self.cache_base.join("index")
+    }
+    
+    /// When a repository's bare clone was last fetched, if ever
+    pub fn last_fetched(&self, name: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.repos.get(name)?;
+        let bare_path = self.bare_path(name);
+        let modified = std::fs::metadata(bare_path.join("FETCH_HEAD"))
+            .or_else(|_| std::fs::metadata(&bare_path))
+            .and_then(|m| m.modified())
+            .ok()?;
+        Some(modified.into())
+    }
+    
     // Private helper methods
     
-    fn clone_bare_repo(&self, owner: &str, repo: &str, bare_path: &Path) -> Result<()> {
-        let url = format!("https://github.com/{}/{}.git", owner, repo);
+    /// Bare clone of a repository, keyed by its registry name so entries for the same
+    /// repository on different forges, owners or mirrors don't share one
+    fn bare_path(&self, name: &str) -> PathBuf {
+        self.bare_repos.join(format!("{}.git", name))
+    }
+    
+    fn repo_forge(&self, name: &str, repo_info: &RepoInfo) -> Box<dyn Forge> {
+        self.forge(name, &repo_info.owner, &repo_info.name, &repo_info.forge)
+    }
+    
+    /// Release discovery for a repository, by the forge it is hosted on. API responses
+    /// are cached under `http/` for `DYNAMO_HTTP_CACHE_TTL` seconds (default 10 minutes)
+    /// and revalidated after, and up to `DYNAMO_MAX_RELEASES` releases are listed.
+    fn forge(&self, name: &str, owner: &str, repo: &str, config: &ForgeConfig) -> Box<dyn Forge> {
+        let cache = HttpCache::new(self.cache_base.join("http"), http_cache_ttl());
+        let max_releases = std::env::var("DYNAMO_MAX_RELEASES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(forge::DEFAULT_MAX_RELEASES);
+        let rest = |cache| RestClient::new().with_cache(cache).with_max_items(max_releases);
+        
+        match config.kind {
+            ForgeKind::GitHub => Box::new(GitHubClient::new(owner, repo)
+                .with_config(config)
+                .with_cache(cache)
+                .with_max_releases(max_releases)),
+            ForgeKind::GitLab => Box::new(GitLabClient::new(owner, repo, config, rest(cache))),
+            ForgeKind::Gitea => Box::new(GiteaClient::new(owner, repo, config, rest(cache))),
+            ForgeKind::Git => Box::new(GitForge::new(
+                self.bare_path(name),
+                owner,
+                repo,
+                config.clone_url.as_deref().unwrap_or_default(),
+            )),
+        }
+    }
+    
+    /// Resolve a version spec (`latest`, `~0.3`, `main@2025-06-01`, ...) to a branch,
+    /// tag or commit. Plain refs are returned unchanged.
+    async fn resolve_version(
+        &self,
+        forge: &dyn Forge,
+        repo: &str,
+        bare_path: &Path,
+        version: &str,
+    ) -> Result<String> {
+        check_ref(version)?;
+        let spec = VersionSpec::parse(version)?;
+        if let Some(resolved) = self.cached_resolution(bare_path, version) {
+            return Ok(resolved);
+        }
+        let resolved = match &spec {
+            VersionSpec::Ref(reference) => return Ok(reference.clone()),
+            VersionSpec::AtDate { branch, date } => self.commit_at_date(bare_path, branch, *date).await?,
+            _ => {
+                // Releases are normally tags too, but include them in case the tag wasn't fetched
+                let mut tags: Vec<String> = self.get_tags(bare_path).await?
+                    .into_iter()
+                    .map(|t| t.name)
+                    .collect();
+                if spec.needs_releases() && !self.offline {
+                    match forge.list_releases().await {
+                        Ok(releases) => {
+                            for release in releases {
+                                if !tags.contains(&release.tag_name) {
+                                    tags.push(release.tag_name);
+                                }
+                            }
+                        },
+                        Err(e) => warn!("Resolving {} of {} from tags only: {}", version, repo, e),
+                    }
+                }
+                spec.select(tags.iter().map(|t| t.as_str()))
+                    .map(|t| t.to_string())
+                    .ok_or_else(|| anyhow::anyhow!("No release of {} matches {}", repo, version))?
+            }
+        };
+        
+        info!("Resolved version {} of {} to {}", version, repo, resolved);
+        self.resolved.lock().unwrap()
+            .insert((bare_path.to_path_buf(), version.to_string()), (resolved.clone(), Instant::now()));
+        Ok(resolved)
+    }
+    
+    /// What a version spec of a repository last resolved to, if that was within the
+    /// HTTP cache TTL
+    pub fn resolved_version(&self, name: &str, version: &str) -> Option<String> {
+        self.cached_resolution(&self.bare_path(name), version)
+    }
+    
+    fn cached_resolution(&self, bare_path: &Path, version: &str) -> Option<String> {
+        let key = (bare_path.to_path_buf(), version.to_string());
+        let mut resolved = self.resolved.lock().unwrap();
+        match resolved.get(&key) {
+            Some((version, at)) if at.elapsed() < http_cache_ttl() => Some(version.clone()),
+            Some(_) => {
+                resolved.remove(&key);
+                None
+            },
+            None => None,
+        }
+    }
+    
+    /// Last commit on a branch made before the end of the given day (UTC)
+    async fn commit_at_date(&self, bare_path: &Path, branch: &str, date: chrono::NaiveDate) -> Result<String> {
+        check_ref(branch)?;
+        let before = format!("{} 00:00:00 +0000", date.succ_opt().unwrap_or(date));
+        for reference in [branch.to_string(), format!("origin/{}", branch)] {
+            let output = Command::new("git")
+                .args(["rev-list", "-1", "--first-parent", "--before", &before, "--end-of-options", &reference, "--"])
+                .current_dir(bare_path)
+                .output()
+                .await
+                .context("Failed to execute git rev-list")?;
+            
+            let commit = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if output.status.success() && !commit.is_empty() {
+                return Ok(commit);
+            }
+        }
         
+        anyhow::bail!("No commit on {} before the end of {}", branch, date)
+    }
+    
+    async fn clone_bare_repo(&self, url: &str, bare_path: &Path) -> Result<()> {
         info!("Cloning bare repository from {}", url);
         
         let output = Command::new("git")
-            .args(&["clone", "--bare", &url, bare_path.to_str().unwrap()])
+            .args(["clone", "--bare", url, bare_path.to_str().unwrap()])
             .output()
+            .await
             .context("Failed to execute git clone")?;
         
         if !output.status.success() {
@@ -211,50 +1121,145 @@ impl RepoManager {
         Ok(())
     }
     
-    fn fetch_updates(&self, bare_path: &Path) -> Result<()> {
+    /// Point the bare repository's origin at the configured clone URL, in case it
+    /// changed since the repository was cloned
+    async fn update_origin(&self, bare_path: &Path, url: &str) -> Result<()> {
+        let output = Command::new("git")
+            .args(["remote", "get-url", "origin"])
+            .current_dir(bare_path)
+            .output()
+            .await
+            .context("Failed to execute git remote")?;
+        if String::from_utf8_lossy(&output.stdout).trim() == url {
+            return Ok(());
+        }
+        
+        info!("Setting origin of {:?} to {}", bare_path, url);
+        let output = Command::new("git")
+            .args(["remote", "set-url", "origin", url])
+            .current_dir(bare_path)
+            .output()
+            .await
+            .context("Failed to execute git remote")?;
+        if !output.status.success() {
+            anyhow::bail!("Failed to set origin URL: {}", String::from_utf8_lossy(&output.stderr));
+        }
+        Ok(())
+    }
+    
+    /// Fetch tags, and branches into `origin/` remote-tracking refs. Bare clones have no
+    /// fetch refspec, so without one only tags would be updated; the local heads can't
+    /// be fetched into directly while worktrees have them checked out.
+    async fn fetch_updates(&self, bare_path: &Path) -> Result<()> {
         let output = Command::new("git")
-            .args(&["fetch", "origin", "--tags"])
+            .args(["fetch", "origin", "--tags", "+refs/heads/*:refs/remotes/origin/*"])
             .current_dir(bare_path)
             .output()
+            .await
             .context("Failed to execute git fetch")?;
         
         if !output.status.success() {
-            warn!("Failed to fetch updates: {}", 
-                String::from_utf8_lossy(&output.stderr));
+            anyhow::bail!("Failed to fetch updates: {}",
+                String::from_utf8_lossy(&output.stderr).trim());
         }
         
         Ok(())
     }
     
-    fn create_worktree(&self, repo: &str, bare_path: &Path, version: &str) -> Result<PathBuf> {
+    /// Fast-forward a worktree that is on a branch to the branch's fetched head, since
+    /// fetches only update `origin/` refs. Returns the old and new commits, or None if
+    /// the worktree is detached or already up to date.
+    async fn fast_forward_worktree(&self, worktree_path: &Path) -> Result<Option<(String, String)>> {
+        let branch = self.get_current_branch(worktree_path).await?;
+        let from = self.get_current_commit(worktree_path).await?;
+        let (Some(branch), Some(from)) = (branch, from) else {
+            return Ok(None);
+        };
+        let upstream = format!("refs/remotes/origin/{}", branch);
+        let Some(to) = self.rev_parse(worktree_path, &upstream).await?.filter(|to| *to != from) else {
+            return Ok(None);
+        };
+        
+        let output = Command::new("git")
+            .args(["merge", "--ff-only", "--quiet", &upstream])
+            .current_dir(worktree_path)
+            .output()
+            .await
+            .context("Failed to execute git merge")?;
+        if !output.status.success() {
+            anyhow::bail!("Failed to fast-forward {:?} to origin/{}: {}", worktree_path, branch,
+                String::from_utf8_lossy(&output.stderr));
+        }
+        Ok(Some((from, to)))
+    }
+    
+    /// The commit a reference points to, or None if it doesn't exist
+    async fn rev_parse(&self, path: &Path, reference: &str) -> Result<Option<String>> {
+        check_ref(reference)?;
+        let output = Command::new("git")
+            .args(["rev-parse", "--verify", "--quiet", "--end-of-options", &format!("{}^{{commit}}", reference)])
+            .current_dir(path)
+            .output()
+            .await
+            .context("Failed to execute git rev-parse")?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        Ok(Some(String::from_utf8_lossy(&output.stdout).trim().to_string()))
+    }
+    
+    /// The commit a resolved version points to, failing if there is none
+    async fn commit_of(&self, path: &Path, version: &str) -> Result<String> {
+        self.rev_parse(path, version).await?
+            .ok_or_else(|| anyhow::anyhow!("Unknown version {}", version))
+    }
+    
+    /// Check out a version in its worktree, leased so that cleanup leaves it alone
+    async fn create_worktree(&self, name: &str, bare_path: &Path, version: &str) -> Result<(PathBuf, WorktreeLease)> {
+        check_ref(version)?;
+        
         // Sanitize version name for filesystem
-        let safe_version = version.replace('/', "_").replace('\\', "_");
-        let worktree_path = self.worktrees.join(format!("{}_{}", repo, safe_version));
+        let safe_version = version.replace(['/', '\\'], "_");
+        let worktree_path = self.worktrees.join(format!("{}_{}", name, safe_version));
+        let lease = WorktreeLease::acquire(&worktree_path).await;
+        self.track_remote_branch(bare_path, version).await?;
         
         // Check if worktree already exists
         if worktree_path.exists() {
             // Checkout the correct version in existing worktree
             let output = Command::new("git")
-                .args(&["checkout", version])
+                .args(["checkout", version, "--"])
                 .current_dir(&worktree_path)
-                .output()?;
+                .output()
+                .await?;
             
             if output.status.success() {
-                return Ok(worktree_path);
+                if let Err(e) = self.fast_forward_worktree(&worktree_path).await {
+                    warn!("{}", e);
+                }
+                self.record_worktree_use(&worktree_path);
+                return Ok((worktree_path, lease));
             }
             
             // If checkout failed, remove and recreate
             warn!("Failed to checkout in existing worktree, recreating");
             std::fs::remove_dir_all(&worktree_path)?;
+            Command::new("git")
+                .args(["worktree", "prune"])
+                .current_dir(bare_path)
+                .output()
+                .await
+                .context("Failed to execute git worktree prune")?;
         }
         
         // Create new worktree
-        info!("Creating worktree for {} at {}", repo, version);
+        info!("Creating worktree for {} at {}", name, version);
         
         let output = Command::new("git")
-            .args(&["worktree", "add", worktree_path.to_str().unwrap(), version])
+            .args(["worktree", "add", "--end-of-options", worktree_path.to_str().unwrap(), version])
             .current_dir(bare_path)
             .output()
+            .await
             .context("Failed to execute git worktree add")?;
         
         if !output.status.success() {
@@ -262,57 +1267,203 @@ impl RepoManager {
                 String::from_utf8_lossy(&output.stderr));
         }
         
-        Ok(worktree_path)
+        // Branches start from the local head, which fetches don't update
+        if let Err(e) = self.fast_forward_worktree(&worktree_path).await {
+            warn!("{}", e);
+        }
+        self.record_worktree_use(&worktree_path);
+        Ok((worktree_path, lease))
     }
     
-    fn get_branches(&self, bare_path: &Path) -> Result<Vec<String>> {
+    /// Create a local branch at `origin/<branch>` for branches that appeared after the
+    /// clone, since fetches only create remote-tracking refs. Git doesn't consider those
+    /// upstreams without a fetch refspec, and `fast_forward_worktree` follows them by
+    /// name anyway. Does nothing for existing branches, tags and commits.
+    async fn track_remote_branch(&self, bare_path: &Path, version: &str) -> Result<()> {
+        let upstream = format!("refs/remotes/origin/{}", version);
+        if self.rev_parse(bare_path, &format!("refs/heads/{}", version)).await?.is_some()
+            || self.rev_parse(bare_path, &upstream).await?.is_none()
+        {
+            return Ok(());
+        }
+        
         let output = Command::new("git")
-            .args(&["branch", "-r"])
+            .args(["branch", "--no-track", version, &upstream])
             .current_dir(bare_path)
-            .output()?;
+            .output()
+            .await
+            .context("Failed to execute git branch")?;
+        if !output.status.success() {
+            anyhow::bail!("Failed to create branch {}: {}", version,
+                String::from_utf8_lossy(&output.stderr));
+        }
+        Ok(())
+    }
+    
+    /// File recording the latest release last seen for each repository, keyed
+    /// by `owner/repo`, so offline startups can use it
+    fn latest_releases_file(&self) -> PathBuf {
+        self.cache_base.join("latest_releases.json")
+    }
+    
+    fn load_latest_releases(&self) -> HashMap<String, String> {
+        read_record(&self.latest_releases_file())
+    }
+    
+    fn record_latest_release(&self, owner: &str, repo: &str, release: &str) {
+        let result = update_record(&self.latest_releases_file(), |releases: &mut HashMap<String, String>| {
+            releases.insert(format!("{}/{}", owner, repo), release.to_string());
+        });
+        if let Err(e) = result {
+            warn!("Failed to record latest release: {}", e);
+        }
+    }
+    
+    /// File recording the releases each repository had at its last refresh, keyed by
+    /// registry name, to tell which releases are new
+    fn known_releases_file(&self) -> PathBuf {
+        self.cache_base.join("known_releases.json")
+    }
+    
+    fn load_known_releases(&self) -> HashMap<String, HashSet<String>> {
+        read_record(&self.known_releases_file())
+    }
+    
+    fn record_known_releases(&self, name: &str, tags: Vec<String>) {
+        let result = update_record(&self.known_releases_file(), |releases: &mut HashMap<String, HashSet<String>>| {
+            releases.insert(name.to_string(), tags.into_iter().collect());
+        });
+        if let Err(e) = result {
+            warn!("Failed to record releases of {}: {}", name, e);
+        }
+    }
+    
+    /// Default version without network access: the last known latest release, else the
+    /// highest cached release tag, else the bare repository's default branch
+    async fn cached_latest_version(&self, owner: &str, repo: &str, bare_path: &Path) -> String {
+        if let Some(release) = self.load_latest_releases().remove(&format!("{}/{}", owner, repo)) {
+            info!("Using last known latest release {} of {}/{}", release, owner, repo);
+            return release;
+        }
         
-        if output.status.success() {
-            let branches = String::from_utf8_lossy(&output.stdout)
-                .lines()
-                .filter_map(|line| {
-                    let branch = line.trim();
-                    if branch.contains("HEAD") {
-                        None
-                    } else {
-                        branch.strip_prefix("origin/")
-                            .map(|b| b.to_string())
-                    }
-                })
-                .collect();
-            Ok(branches)
-        } else {
-            Ok(Vec::new())
+        let tags: Vec<String> = self.get_tags(bare_path).await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        if let Some(tag) = VersionSpec::Latest.select(tags.iter().map(|t| t.as_str())) {
+            info!("No known latest release of {}/{}, using highest cached tag {}", owner, repo, tag);
+            return tag.to_string();
         }
+        
+        self.bare_default_branch(bare_path).await
     }
     
-    fn get_tags(&self, bare_path: &Path) -> Result<Vec<String>> {
+    /// Branch HEAD of the bare repository points to, which is the remote's default
+    /// branch at clone time
+    async fn bare_default_branch(&self, bare_path: &Path) -> String {
         let output = Command::new("git")
-            .args(&["tag"])
+            .args(["symbolic-ref", "--short", "HEAD"])
             .current_dir(bare_path)
-            .output()?;
+            .output()
+            .await;
+        match output {
+            Ok(output) if output.status.success() => {
+                String::from_utf8_lossy(&output.stdout).trim().to_string()
+            },
+            _ => "main".to_string(),
+        }
+    }
+    
+    /// File recording when each worktree was last used, keyed by directory name
+    fn usage_file(&self) -> PathBuf {
+        self.cache_base.join("worktree_usage.json")
+    }
+    
+    fn load_worktree_usage(&self) -> HashMap<String, i64> {
+        read_record(&self.usage_file())
+    }
+    
+    fn record_worktree_use(&self, worktree_path: &Path) {
+        let Some(name) = worktree_path.file_name() else {
+            return;
+        };
+        let result = update_record(&self.usage_file(), |usage: &mut HashMap<String, i64>| {
+            usage.insert(name.to_string_lossy().to_string(), chrono::Utc::now().timestamp());
+        });
+        if let Err(e) = result {
+            warn!("Failed to record worktree usage: {}", e);
+        }
+    }
+    
+    /// Branches of a bare repository, from its own heads and any `origin/` remote-tracking refs
+    async fn get_branches(&self, bare_path: &Path) -> Result<Vec<BranchInfo>> {
+        let refs = self.list_refs(bare_path, &["refs/heads", "refs/remotes/origin"], "committerdate").await?;
         
-        if output.status.success() {
-            let tags = String::from_utf8_lossy(&output.stdout)
-                .lines()
-                .map(|line| line.trim().to_string())
-                .filter(|t| !t.is_empty())
-                .collect();
-            Ok(tags)
-        } else {
-            Ok(Vec::new())
+        // A remote-tracking ref is at least as fresh as the local head of the same name
+        let mut branches: HashMap<String, BranchInfo> = HashMap::new();
+        for (refname, commit, date) in refs {
+            let name = match refname.strip_prefix("refs/remotes/origin/") {
+                Some(name) => name,
+                None => refname.trim_start_matches("refs/heads/"),
+            };
+            if name == "HEAD" {
+                continue;
+            }
+            let remote = refname.starts_with("refs/remotes/");
+            if remote || !branches.contains_key(name) {
+                branches.insert(name.to_string(), BranchInfo {
+                    name: name.to_string(),
+                    commit,
+                    last_commit_date: date,
+                });
+            }
+        }
+        Ok(branches.into_values().collect())
+    }
+    
+    async fn get_tags(&self, bare_path: &Path) -> Result<Vec<TagInfo>> {
+        let refs = self.list_refs(bare_path, &["refs/tags"], "creatordate").await?;
+        Ok(refs.into_iter()
+            .map(|(refname, commit, date)| {
+                let name = refname.trim_start_matches("refs/tags/").to_string();
+                let prerelease = versions::parse_tag(&name).is_some_and(|v| !v.pre.is_empty());
+                TagInfo { name, commit, date, prerelease }
+            })
+            .collect())
+    }
+    
+    /// `(refname, commit, date)` for refs under the given prefixes, with the date field
+    /// formatted as strict ISO 8601 in the committer's or tagger's time zone, so dates
+    /// only compare correctly through `parse_date`
+    async fn list_refs(&self, bare_path: &Path, prefixes: &[&str], date_field: &str) -> Result<Vec<(String, String, String)>> {
+        let format = format!("--format=%(refname)%09%(objectname)%09%({}:iso-strict)", date_field);
+        let output = Command::new("git")
+            .arg("for-each-ref")
+            .arg(&format)
+            .args(prefixes)
+            .current_dir(bare_path)
+            .output()
+            .await?;
+        
+        if !output.status.success() {
+            return Ok(Vec::new());
         }
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, '\t');
+                Some((fields.next()?.to_string(), fields.next()?.to_string(), fields.next()?.to_string()))
+            })
+            .collect())
     }
     
-    fn get_current_commit(&self, worktree_path: &Path) -> Result<Option<String>> {
+    async fn get_current_commit(&self, worktree_path: &Path) -> Result<Option<String>> {
         let output = Command::new("git")
-            .args(&["rev-parse", "HEAD"])
+            .args(["rev-parse", "HEAD"])
             .current_dir(worktree_path)
-            .output()?;
+            .output()
+            .await?;
         
         if output.status.success() {
             Ok(Some(String::from_utf8_lossy(&output.stdout).trim().to_string()))
@@ -321,11 +1472,12 @@ impl RepoManager {
         }
     }
     
-    fn get_current_branch(&self, worktree_path: &Path) -> Result<Option<String>> {
+    async fn get_current_branch(&self, worktree_path: &Path) -> Result<Option<String>> {
         let output = Command::new("git")
-            .args(&["rev-parse", "--abbrev-ref", "HEAD"])
+            .args(["rev-parse", "--abbrev-ref", "HEAD"])
             .current_dir(worktree_path)
-            .output()?;
+            .output()
+            .await?;
         
         if output.status.success() {
             let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
@@ -339,10 +1491,460 @@ impl RepoManager {
         }
     }
     
-    /// Clean up old worktrees, keeping the most recent N
-    pub fn cleanup_old_worktrees(&mut self, keep_recent: usize) -> Result<()> {
-        // TODO: Implement cleanup logic
-        // List worktrees, sort by access time, remove old ones
+    /// Clean up old worktrees, least recently used first.
+    ///
+    /// Keeps at most `keep_recent` inactive worktrees, then keeps evicting the least
+    /// recently used ones while the cache exceeds `max_bytes`. Worktrees that are
+    /// current, loaded in the index or being checked out hold a `WorktreeLease` and are
+    /// never removed. Meant to run on a snapshot of the manager, so no lock is held
+    /// while the worktrees are measured and removed.
+    pub async fn cleanup_old_worktrees(&self, keep_recent: usize, max_bytes: Option<u64>) -> Result<CleanupReport> {
+        let usage = self.load_worktree_usage();
+        let worktrees = self.worktrees.clone();
+        let scanned = tokio::task::spawn_blocking(move || scan_worktrees(&worktrees)).await??;
+        
+        let mut candidates = Vec::new();
+        let mut total_bytes = 0;
+        for (path, bytes, modified) in scanned {
+            total_bytes += bytes;
+            if WORKTREE_USE.lock().unwrap().held.contains_key(&path) {
+                continue;
+            }
+            // Fall back to the directory's modification time for worktrees never recorded
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            let last_used = usage.get(&name).copied().unwrap_or(modified);
+            candidates.push((path, last_used, bytes));
+        }
+        
+        // Most recently used first, so evictions come off the end
+        candidates.sort_by_key(|c| std::cmp::Reverse(c.1));
+        
+        let mut removed = Vec::new();
+        let mut skipped = 0;
+        let mut errors = Vec::new();
+        while let Some((path, last_used, bytes)) = candidates.last().cloned() {
+            let over_count = candidates.len() > keep_recent;
+            let over_budget = max_bytes.is_some_and(|max| total_bytes > max);
+            if !over_count && !over_budget {
+                break;
+            }
+            candidates.pop();
+            
+            // Leased since it was measured; carry on with the rest
+            let Some(_removal) = Removal::start(&path) else {
+                skipped += 1;
+                continue;
+            };
+            if let Err(e) = self.remove_worktree(&path).await {
+                warn!("Failed to remove worktree {:?}: {}", path, e);
+                errors.push(format!("Failed to remove worktree {:?}: {}", path, e));
+                skipped += 1;
+                continue;
+            }
+            total_bytes -= bytes;
+            info!("Removed worktree {:?} ({} bytes)", path, bytes);
+            removed.push(RemovedWorktree {
+                path: path.to_string_lossy().to_string(),
+                last_used: chrono::DateTime::from_timestamp(last_used, 0)
+                    .map(|t| t.to_rfc3339())
+                    .unwrap_or_default(),
+                bytes,
+            });
+        }
+        
+        // Drop bookkeeping for worktrees that no longer exist
+        let written = update_record(&self.usage_file(), |usage: &mut HashMap<String, i64>| {
+            usage.retain(|name, _| self.worktrees.join(name).exists());
+        });
+        if let Err(e) = written {
+            warn!("Failed to update worktree usage: {}", e);
+            errors.push(format!("Failed to update worktree usage: {}", e));
+        }
+        
+        let over_budget = max_bytes.is_some_and(|max| total_bytes > max);
+        if over_budget {
+            warn!("Worktree cache still uses {} bytes; only worktrees in use remain", total_bytes);
+        }
+        
+        Ok(CleanupReport {
+            freed_bytes: removed.iter().map(|r| r.bytes).sum(),
+            removed,
+            kept: candidates.len() + skipped,
+            total_bytes,
+            over_budget,
+            errors,
+        })
+    }
+    
+    /// Remove a worktree through git so the bare repository forgets it, falling back to
+    /// deleting the directory and pruning if git no longer recognizes it
+    async fn remove_worktree(&self, worktree_path: &Path) -> Result<()> {
+        let bare_path = std::fs::read_to_string(worktree_path.join(".git"))
+            .ok()
+            .and_then(|gitfile| {
+                // `gitdir: <bare>/worktrees/<name>`
+                let gitdir = PathBuf::from(gitfile.trim().strip_prefix("gitdir:")?.trim());
+                gitdir.parent()?.parent().map(Path::to_path_buf)
+            });
+        
+        if let Some(bare_path) = &bare_path {
+            let output = Command::new("git")
+                .args(["worktree", "remove", "--force", worktree_path.to_str().unwrap()])
+                .current_dir(bare_path)
+                .output()
+                .await
+                .context("Failed to execute git worktree remove")?;
+            if output.status.success() {
+                return Ok(());
+            }
+            warn!("git worktree remove failed for {:?}: {}", worktree_path,
+                String::from_utf8_lossy(&output.stderr));
+        }
+        
+        if worktree_path.exists() {
+            std::fs::remove_dir_all(worktree_path)?;
+        }
+        
+        // Let every bare repository drop its record of the deleted worktree
+        for entry in std::fs::read_dir(&self.bare_repos)? {
+            let bare = entry?.path();
+            let output = Command::new("git")
+                .args(["worktree", "prune"])
+                .current_dir(&bare)
+                .output()
+                .await?;
+            if !output.status.success() {
+                warn!("git worktree prune failed in {:?}: {}", bare,
+                    String::from_utf8_lossy(&output.stderr));
+            }
+        }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Parse an ISO 8601 date as git and the forges format them. Strings can't be compared
+/// directly, as their UTC offsets differ.
+fn parse_date(date: &str) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    chrono::DateTime::parse_from_rfc3339(date).ok()
+}
+
+/// How long forge API responses, and version specs resolved from them, are reused
+fn http_cache_ttl() -> Duration {
+    let ttl = std::env::var("DYNAMO_HTTP_CACHE_TTL")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(600);
+    Duration::from_secs(ttl)
+}
+
+/// Reject versions git would parse as options, e.g. `--orphan=x` or `--output=<file>`
+fn check_ref(reference: &str) -> Result<()> {
+    if reference.is_empty() || reference.starts_with('-') {
+        anyhow::bail!("Invalid version {:?}", reference);
+    }
+    Ok(())
+}
+
+/// Contents of a JSON record file, empty if it is missing or unreadable
+fn read_record<T: Default + serde::de::DeserializeOwned>(path: &Path) -> T {
+    std::fs::read(path)
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Read, modify and write back a JSON record file under `RECORD_LOCK`. The new contents
+/// are written to a temporary file first, so readers never see a truncated record.
+fn update_record<T>(path: &Path, update: impl FnOnce(&mut T)) -> Result<()>
+where
+    T: Default + serde::Serialize + serde::de::DeserializeOwned,
+{
+    let _guard = RECORD_LOCK.lock().unwrap();
+    let mut record: T = read_record(path);
+    update(&mut record);
+    let tmp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+    std::fs::write(&tmp, serde_json::to_vec_pretty(&record)?)
+        .with_context(|| format!("Failed to write {:?}", tmp))?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Worktree directories with their size and modification time (Unix time)
+fn scan_worktrees(worktrees: &Path) -> Result<Vec<(PathBuf, u64, i64)>> {
+    let mut scanned = Vec::new();
+    for entry in std::fs::read_dir(worktrees)? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let modified = std::fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .map(|t| chrono::DateTime::<chrono::Utc>::from(t).timestamp())
+            .unwrap_or(0);
+        let bytes = dir_size(&path);
+        scanned.push((path, bytes, modified));
+    }
+    Ok(scanned)
+}
+
+/// Sort the output of `git diff --name-status -M -z` into the documentation files
+/// selected by `is_doc` that were added, removed, modified or renamed. A rename into or
+/// out of the documentation counts as an addition or removal.
+fn classify_changes(name_status: &str, is_doc: impl Fn(&str) -> bool, diff: &mut DocsDiff) {
+    let mut fields = name_status.split('\0').filter(|f| !f.is_empty());
+    while let Some(status) = fields.next() {
+        let Some(old_path) = fields.next() else { break };
+        match status.chars().next() {
+            Some('R') => {
+                let Some(new_path) = fields.next() else { break };
+                match (is_doc(old_path), is_doc(new_path)) {
+                    (true, true) => diff.renamed.push(RenamedFile {
+                        from: old_path.to_string(),
+                        to: new_path.to_string(),
+                        modified: status != "R100",
+                    }),
+                    (true, false) => diff.removed.push(old_path.to_string()),
+                    (false, true) => diff.added.push(new_path.to_string()),
+                    (false, false) => {}
+                }
+            },
+            Some('C') => {
+                let Some(new_path) = fields.next() else { break };
+                if is_doc(new_path) {
+                    diff.added.push(new_path.to_string());
+                }
+            },
+            Some('A') if is_doc(old_path) => diff.added.push(old_path.to_string()),
+            Some('D') if is_doc(old_path) => diff.removed.push(old_path.to_string()),
+            Some('M') | Some('T') if is_doc(old_path) => diff.modified.push(old_path.to_string()),
+            _ => {}
+        }
+    }
+}
+
+/// Total size of the files under a directory
+fn dir_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{git, git_at, remote_repo, TempDir};
+    
+    /// A manager over worktrees `r_<name>` of the given sizes, used `age` seconds ago
+    fn worktrees(dir: &TempDir, worktrees: &[(&str, usize, i64)]) -> RepoManager {
+        let manager = RepoManager::with_cache_dir(dir.path().to_path_buf(), true).unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let mut usage = HashMap::new();
+        for (name, bytes, age) in worktrees {
+            let path = manager.worktrees.join(format!("r_{}", name));
+            std::fs::create_dir_all(&path).unwrap();
+            std::fs::write(path.join("README.md"), vec![b'x'; *bytes]).unwrap();
+            usage.insert(format!("r_{}", name), now - age);
+        }
+        std::fs::write(manager.usage_file(), serde_json::to_vec(&usage).unwrap()).unwrap();
+        manager
+    }
+    
+    fn removed(report: &CleanupReport) -> Vec<String> {
+        report.removed
+            .iter()
+            .map(|r| Path::new(&r.path).file_name().unwrap().to_string_lossy().to_string())
+            .collect()
+    }
+    
+    #[tokio::test]
+    async fn cleanup_keeps_the_most_recently_used_worktrees() {
+        let dir = TempDir::new("cleanup-keep-recent");
+        let manager = worktrees(&dir, &[("v1", 10, 400), ("v2", 10, 100), ("v3", 10, 300), ("v4", 10, 200)]);
+        
+        let report = manager.cleanup_old_worktrees(2, None).await.unwrap();
+        assert_eq!(removed(&report), ["r_v1", "r_v3"]);
+        assert_eq!(report.kept, 2);
+        assert_eq!(report.freed_bytes, 20);
+        assert!(report.errors.is_empty());
+        assert!(!manager.worktrees.join("r_v1").exists());
+        assert!(manager.worktrees.join("r_v2").exists());
+        
+        // Removed worktrees are dropped from the bookkeeping
+        let usage = manager.load_worktree_usage();
+        assert_eq!(usage.len(), 2);
+        assert!(usage.contains_key("r_v4"));
+    }
+    
+    #[tokio::test]
+    async fn cleanup_evicts_until_the_budget_fits_and_spares_leased_worktrees() {
+        let dir = TempDir::new("cleanup-budget");
+        let manager = worktrees(&dir, &[("v1", 100, 400), ("v2", 100, 300), ("v3", 100, 200), ("v4", 100, 100)]);
+        let _lease = WorktreeLease::hold(&manager.worktrees.join("r_v1"));
+        
+        let report = manager.cleanup_old_worktrees(10, Some(250)).await.unwrap();
+        assert_eq!(removed(&report), ["r_v2", "r_v3"]);
+        assert_eq!(report.total_bytes, 200);
+        assert!(!report.over_budget);
+        assert!(manager.worktrees.join("r_v1").exists());
+        
+        // Only the leased worktree is left to evict
+        let report = manager.cleanup_old_worktrees(0, Some(50)).await.unwrap();
+        assert_eq!(removed(&report), ["r_v4"]);
+        assert_eq!(report.total_bytes, 100);
+        assert!(report.over_budget);
+        assert!(manager.worktrees.join("r_v1").exists());
+    }
+    
+    #[tokio::test]
+    async fn refresh_reports_new_tags_releases_and_branches() {
+        let dir = TempDir::new("refresh-report");
+        let (remote, forge) = remote_repo(dir.path());
+        let mut manager = RepoManager::with_cache_dir(dir.path().join("cache"), false).unwrap();
+        manager.setup_repo("r", "o", "r", &forge, Some("v1.0.0"), false).await.unwrap();
+        let main_before = git(&remote, &["rev-parse", "main"]);
+        
+        git(&remote, &["commit", "-q", "--allow-empty", "-m", "Second"]);
+        git_at(&remote, "2025-02-01T00:00:00Z", &["tag", "-a", "v1.1.0", "-m", "Second release"]);
+        git(&remote, &["tag", "nightly"]);
+        git(&remote, &["branch", "feature"]);
+        let main_after = git(&remote, &["rev-parse", "main"]);
+        
+        let reports = manager.refresh().await.unwrap();
+        assert_eq!(reports.len(), 1);
+        let report = &reports[0];
+        assert!(report.fetched, "{:?}", report.error);
+        assert_eq!(report.error, None);
+        assert_eq!(report.new_tags, ["v1.1.0", "nightly"]);
+        // Only annotated tags are releases of a plain git remote
+        assert_eq!(report.new_releases, ["v1.1.0"]);
+        let branches: Vec<(&str, Option<&str>, &str)> = report.updated_branches
+            .iter()
+            .map(|b| (b.name.as_str(), b.old.as_deref(), b.new.as_str()))
+            .collect();
+        assert_eq!(
+            branches,
+            [("feature", None, main_after.as_str()), ("main", Some(main_before.as_str()), main_after.as_str())]
+        );
+        assert_eq!(report.active_version, "v1.0.0");
+        assert!(report.behind);
+        assert_eq!(report.latest.as_deref(), Some("v1.1.0"));
+        assert!(report.fast_forward.is_none());
+        
+        // Nothing is new the second time, though the release is still ahead
+        let report = &manager.refresh().await.unwrap()[0];
+        assert!(report.new_tags.is_empty() && report.new_releases.is_empty());
+        assert!(report.updated_branches.is_empty());
+        assert_eq!(report.latest.as_deref(), Some("v1.1.0"));
+    }
+    
+    #[tokio::test]
+    async fn refresh_reports_fetch_failures() {
+        let dir = TempDir::new("refresh-unreachable");
+        let (remote, forge) = remote_repo(dir.path());
+        let mut manager = RepoManager::with_cache_dir(dir.path().join("cache"), false).unwrap();
+        manager.setup_repo("r", "o", "r", &forge, Some("main"), false).await.unwrap();
+        std::fs::remove_dir_all(&remote).unwrap();
+        
+        let report = &manager.refresh().await.unwrap()[0];
+        assert!(!report.fetched);
+        assert!(report.error.as_deref().unwrap().contains("Failed to fetch updates"));
+        
+        let offline = RepoManager::with_cache_dir(dir.path().join("cache"), true).unwrap();
+        assert!(offline.refresh().await.is_err());
+    }
+    
+    #[test]
+    fn concurrent_record_updates_keep_every_entry() {
+        let dir = TempDir::new("record-updates");
+        let manager = RepoManager::with_cache_dir(dir.path().to_path_buf(), true).unwrap();
+        let threads: Vec<_> = (0..8)
+            .map(|n| {
+                let manager = manager.clone();
+                std::thread::spawn(move || {
+                    for i in 0..10 {
+                        manager.record_worktree_use(&manager.worktrees.join(format!("r_v{}.{}", n, i)));
+                        manager.record_known_releases(&format!("r{}", n), vec![format!("v{}", i)]);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        
+        assert_eq!(manager.load_worktree_usage().len(), 80);
+        let known = manager.load_known_releases();
+        assert_eq!(known.len(), 8);
+        assert!(known.values().all(|tags| tags.contains("v9")));
+    }
+    
+    #[test]
+    fn classifies_documentation_changes() {
+        let include = ["docs/**/*.md".to_string()];
+        let exclude = ["docs/drafts/**".to_string()];
+        let rules = crate::rules::IndexRules::new(&include, &exclude, &Default::default()).unwrap();
+        let is_doc = |path: &str| path.ends_with(".md") && rules.matches(path);
+        let name_status = [
+            "A", "docs/new.md",
+            "D", "docs/old.md",
+            "M", "docs/changed.md",
+            "M", "src/main.rs",
+            "A", "docs/drafts/wip.md",
+            "R100", "docs/a.md", "docs/b.md",
+            "R087", "docs/c.md", "docs/guide/c.md",
+            "R100", "docs/drafts/done.md", "docs/done.md",
+            "R095", "docs/gone.md", "notes/gone.md",
+            "C075", "docs/new.md", "docs/copy.md",
+            "A", "docs/with\ttab and spaces.md",
+            "",
+        ]
+        .join("\0");
+        let mut diff = DocsDiff {
+            repo: "r".to_string(),
+            from: "v1".to_string(),
+            to: "v2".to_string(),
+            added: Vec::new(),
+            removed: Vec::new(),
+            modified: Vec::new(),
+            renamed: Vec::new(),
+            diff: None,
+        };
+        classify_changes(&name_status, is_doc, &mut diff);
+        
+        assert_eq!(diff.added, ["docs/new.md", "docs/done.md", "docs/copy.md", "docs/with\ttab and spaces.md"]);
+        assert_eq!(diff.removed, ["docs/old.md", "docs/gone.md"]);
+        assert_eq!(diff.modified, ["docs/changed.md"]);
+        let renamed: Vec<(&str, &str, bool)> = diff.renamed
+            .iter()
+            .map(|r| (r.from.as_str(), r.to.as_str(), r.modified))
+            .collect();
+        assert_eq!(renamed, [("docs/a.md", "docs/b.md", false), ("docs/c.md", "docs/guide/c.md", true)]);
+    }
+    
+    #[tokio::test]
+    async fn leases_wait_for_removals_to_finish() {
+        let path = PathBuf::from("/nonexistent/dynamo-mcp-test/r_v1");
+        let removal = Removal::start(&path).unwrap();
+        assert!(Removal::start(&path).is_none());
+        
+        let acquire = tokio::spawn({
+            let path = path.clone();
+            async move { WorktreeLease::acquire(&path).await }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!acquire.is_finished());
+        drop(removal);
+        let lease = acquire.await.unwrap();
+        
+        // Leased worktrees can't be removed until every lease is dropped
+        let clone = lease.clone();
+        drop(lease);
+        assert!(Removal::start(&path).is_none());
+        drop(clone);
+        assert!(Removal::start(&path).is_some());
+    }
+}
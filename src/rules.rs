@@ -0,0 +1,210 @@
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::collections::BTreeMap;
+
+/// Default include globs for repositories that don't configure their own
+pub const DEFAULT_INCLUDE: &[&str] = &["README.md", "docs/**/*.md"];
+
+/// Default exclude globs for repositories that don't configure their own
+pub const DEFAULT_EXCLUDE: &[&str] = &["**/node_modules/**", "**/target/**", "**/third_party/**"];
+
+/// Include/exclude globs selecting which files of a repository get indexed, and how
+/// they are categorized.
+///
+/// Globs are matched against paths relative to the repository root using `/`
+/// separators; `*` does not cross directory boundaries, `**` does.
+#[derive(Debug, Clone)]
+pub struct IndexRules {
+    include: GlobSet,
+    exclude: GlobSet,
+    /// Directories whose whole contents are excluded, from exclude globs ending in `/**`
+    exclude_dirs: GlobSet,
+    /// Directory prefix to category overrides, longest prefix first
+    categories: Vec<(String, String)>,
+    fingerprint: String,
+}
+
+impl IndexRules {
+    pub fn new(
+        include: &[String],
+        exclude: &[String],
+        categories: &BTreeMap<String, String>,
+    ) -> Result<Self> {
+        let mut category_list: Vec<(String, String)> = categories
+            .iter()
+            .map(|(prefix, category)| (prefix.trim_matches('/').to_string(), category.clone()))
+            .collect();
+        category_list.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+
+        let fingerprint = format!(
+            "include={};exclude={};categories={}",
+            include.join(","),
+            exclude.join(","),
+            category_list
+                .iter()
+                .map(|(p, c)| format!("{}={}", p, c))
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+
+        let exclude_dirs: Vec<String> = exclude
+            .iter()
+            .filter_map(|pattern| pattern.strip_suffix("/**"))
+            .map(str::to_string)
+            .collect();
+
+        Ok(Self {
+            include: build_glob_set(include)?,
+            exclude: build_glob_set(exclude)?,
+            exclude_dirs: build_glob_set(&exclude_dirs)?,
+            categories: category_list,
+            fingerprint,
+        })
+    }
+
+    /// String identifying the rule set, so indexes built with other rules can be detected
+    pub fn fingerprint(&self) -> String {
+        self.fingerprint.clone()
+    }
+
+    /// Whether a repository-relative path should be indexed
+    pub fn matches(&self, relative: &str) -> bool {
+        self.include.is_match(relative) && !self.exclude.is_match(relative)
+    }
+
+    /// Whether everything below a repository-relative directory is excluded, so it
+    /// needn't be walked
+    pub fn excludes_dir(&self, relative: &str) -> bool {
+        self.exclude_dirs.is_match(relative)
+    }
+
+    /// Category for a repository-relative path: the configured category of the longest
+    /// matching directory prefix, otherwise one derived from the directory layout
+    pub fn category_for(&self, relative: &str) -> String {
+        self.categories
+            .iter()
+            .find(|(prefix, _)| {
+                prefix.is_empty()
+                    || relative
+                        .strip_prefix(prefix.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+            .map(|(_, category)| category.clone())
+            .unwrap_or_else(|| derived_category(relative))
+    }
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid glob pattern: {}", pattern))?;
+        builder.add(glob);
+    }
+    Ok(builder.build()?)
+}
+
+/// Category derived from where a file lives in the repository:
+/// the first directory below `docs/` for documentation (e.g. `architecture`),
+/// the top-level directory otherwise (e.g. `components`), and
+/// `getting_started` for files at the repository root.
+fn derived_category(relative: &str) -> String {
+    let dirs: Vec<&str> = relative.split('/').collect();
+    let dirs = &dirs[..dirs.len() - 1];
+    match dirs {
+        [] => "getting_started".to_string(),
+        ["docs"] => "docs".to_string(),
+        ["docs", sub, ..] => sub.to_string(),
+        [top, ..] => top.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|p| p.to_string()).collect()
+    }
+
+    fn default_rules() -> IndexRules {
+        IndexRules::new(
+            &strings(DEFAULT_INCLUDE),
+            &strings(DEFAULT_EXCLUDE),
+            &BTreeMap::new(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn matches_included_and_not_excluded_paths() {
+        let rules = default_rules();
+        assert!(rules.matches("README.md"));
+        assert!(rules.matches("docs/index.md"));
+        assert!(rules.matches("docs/guides/deep/setup.md"));
+        assert!(!rules.matches("docs/image.png"));
+        assert!(!rules.matches("components/README.md"));
+        assert!(!rules.matches("docs/node_modules/pkg/README.md"));
+        assert!(!rules.matches("docs/third_party/notes.md"));
+    }
+
+    #[test]
+    fn single_star_does_not_cross_directories() {
+        let rules = IndexRules::new(&strings(&["*.md"]), &[], &BTreeMap::new()).unwrap();
+        assert!(rules.matches("CHANGELOG.md"));
+        assert!(!rules.matches("docs/CHANGELOG.md"));
+    }
+
+    #[test]
+    fn excludes_whole_directories_from_trailing_double_star_globs() {
+        let rules = IndexRules::new(
+            &strings(DEFAULT_INCLUDE),
+            &strings(&["**/node_modules/**", "docs/drafts/**", "**/*.tmp.md"]),
+            &BTreeMap::new(),
+        )
+        .unwrap();
+        assert!(rules.excludes_dir("node_modules"));
+        assert!(rules.excludes_dir("web/node_modules"));
+        assert!(rules.excludes_dir("docs/drafts"));
+        assert!(!rules.excludes_dir("docs"));
+        assert!(!rules.excludes_dir("docs/drafts-old"));
+        assert!(!rules.matches("docs/scratch.tmp.md"));
+    }
+
+    #[test]
+    fn rejects_invalid_globs() {
+        assert!(IndexRules::new(&strings(&["docs/[.md"]), &[], &BTreeMap::new()).is_err());
+    }
+
+    #[test]
+    fn derives_categories_from_the_layout() {
+        let rules = default_rules();
+        assert_eq!(rules.category_for("README.md"), "getting_started");
+        assert_eq!(rules.category_for("docs/index.md"), "docs");
+        assert_eq!(rules.category_for("docs/architecture/planner.md"), "architecture");
+        assert_eq!(rules.category_for("components/router/README.md"), "components");
+    }
+
+    #[test]
+    fn prefers_the_longest_configured_prefix() {
+        let categories = BTreeMap::from([
+            ("docs/".to_string(), "reference".to_string()),
+            ("/docs/guides".to_string(), "guide".to_string()),
+        ]);
+        let rules = IndexRules::new(&strings(DEFAULT_INCLUDE), &[], &categories).unwrap();
+        assert_eq!(rules.category_for("docs/guides/start.md"), "guide");
+        assert_eq!(rules.category_for("docs/api.md"), "reference");
+        // Prefixes match whole directory names only
+        assert_eq!(rules.category_for("docs-old/api.md"), "docs-old");
+        assert_eq!(rules.category_for("README.md"), "getting_started");
+    }
+
+    #[test]
+    fn fingerprint_changes_with_the_rules() {
+        let other = IndexRules::new(&strings(&["**/*.md"]), &[], &BTreeMap::new()).unwrap();
+        assert_ne!(default_rules().fingerprint(), other.fingerprint());
+        assert_eq!(default_rules().fingerprint(), default_rules().fingerprint());
+    }
+}
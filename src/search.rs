@@ -0,0 +1,324 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// BM25 term-frequency saturation parameter
+const K1: f32 = 1.2;
+/// BM25 document-length normalization parameter
+const B: f32 = 0.75;
+/// Weight applied to matches in the title relative to the body
+const TITLE_BOOST: f32 = 2.5;
+
+/// Split text into lowercase alphanumeric tokens
+pub fn tokenize(text: &str) -> Vec<String> {
+    token_spans(text).into_iter().map(|(_, token)| token).collect()
+}
+
+/// Like [`tokenize`], but also returns the byte range of each token in `text`
+pub fn token_spans(text: &str) -> Vec<(Range<usize>, String)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                spans.push((s..i, text[s..i].to_lowercase()));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s..text.len(), text[s..].to_lowercase()));
+    }
+    spans
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TermFreq {
+    title: u32,
+    content: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Unit {
+    title_len: usize,
+    content_len: usize,
+    /// Distinct terms occurring in the unit, used to remove it from the postings
+    terms: Vec<String>,
+}
+
+/// Pre-tokenized form of an indexed entry, used to persist and restore the index
+/// without re-reading and re-tokenizing documents
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedUnit {
+    pub id: String,
+    title_len: usize,
+    content_len: usize,
+    terms: Vec<(String, TermFreq)>,
+}
+
+/// Inverted index over document titles and contents, ranked with BM25
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    postings: HashMap<String, HashMap<String, TermFreq>>,
+    units: HashMap<String, Unit>,
+    total_title_len: usize,
+    total_content_len: usize,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a document to the index, replacing any previous entry with the same id
+    pub fn insert(&mut self, id: &str, title: &str, content: &str) {
+        let title_tokens = tokenize(title);
+        let content_tokens = tokenize(content);
+
+        let mut freqs: HashMap<String, TermFreq> = HashMap::new();
+        for token in &title_tokens {
+            freqs.entry(token.clone()).or_default().title += 1;
+        }
+        for token in &content_tokens {
+            freqs.entry(token.clone()).or_default().content += 1;
+        }
+
+        self.import(IndexedUnit {
+            id: id.to_string(),
+            title_len: title_tokens.len(),
+            content_len: content_tokens.len(),
+            terms: freqs.into_iter().collect(),
+        });
+    }
+
+    /// Add a pre-tokenized entry, replacing any previous entry with the same id
+    pub fn import(&mut self, unit: IndexedUnit) {
+        self.remove(&unit.id);
+
+        let mut terms = Vec::with_capacity(unit.terms.len());
+        for (term, tf) in unit.terms {
+            self.postings
+                .entry(term.clone())
+                .or_default()
+                .insert(unit.id.clone(), tf);
+            terms.push(term);
+        }
+
+        self.total_title_len += unit.title_len;
+        self.total_content_len += unit.content_len;
+        self.units.insert(
+            unit.id,
+            Unit {
+                title_len: unit.title_len,
+                content_len: unit.content_len,
+                terms,
+            },
+        );
+    }
+
+    /// Pre-tokenized form of an entry, for persisting the index
+    pub fn export(&self, id: &str) -> Option<IndexedUnit> {
+        let unit = self.units.get(id)?;
+        Some(IndexedUnit {
+            id: id.to_string(),
+            title_len: unit.title_len,
+            content_len: unit.content_len,
+            terms: unit
+                .terms
+                .iter()
+                .filter_map(|term| Some((term.clone(), self.term_freq(id, term)?)))
+                .collect(),
+        })
+    }
+
+    /// Remove a document from the index
+    pub fn remove(&mut self, id: &str) {
+        let Some(unit) = self.units.remove(id) else {
+            return;
+        };
+        self.total_title_len -= unit.title_len;
+        self.total_content_len -= unit.content_len;
+        for term in &unit.terms {
+            if let Some(docs) = self.postings.get_mut(term) {
+                docs.remove(id);
+                if docs.is_empty() {
+                    self.postings.remove(term);
+                }
+            }
+        }
+    }
+
+    /// BM25 scores for every document containing at least one of the terms
+    pub fn score(&self, terms: &[String]) -> HashMap<String, f32> {
+        let mut scores: HashMap<String, f32> = HashMap::new();
+        for term in terms {
+            let Some(docs) = self.postings.get(term) else {
+                continue;
+            };
+            let idf = self.idf(docs.len());
+            for (id, tf) in docs {
+                let unit = &self.units[id];
+                let score = idf
+                    * (TITLE_BOOST * bm25_tf(tf.title, unit.title_len, self.avg_title_len())
+                        + bm25_tf(tf.content, unit.content_len, self.avg_content_len()));
+                *scores.entry(id.clone()).or_default() += score;
+            }
+        }
+        scores
+    }
+
+    /// Ids of the documents the term occurs in
+    pub fn units_with<'a>(&'a self, term: &str) -> impl Iterator<Item = &'a str> + 'a {
+        self.postings
+            .get(term)
+            .into_iter()
+            .flat_map(|docs| docs.keys().map(String::as_str))
+    }
+
+    /// Whether the term occurs in the document's title
+    pub fn in_title(&self, id: &str, term: &str) -> bool {
+        self.term_freq(id, term).is_some_and(|tf| tf.title > 0)
+    }
+
+    /// Whether the term occurs in the document's title or content
+    pub fn in_document(&self, id: &str, term: &str) -> bool {
+        self.term_freq(id, term).is_some()
+    }
+
+    fn term_freq(&self, id: &str, term: &str) -> Option<TermFreq> {
+        self.postings.get(term)?.get(id).copied()
+    }
+
+    fn idf(&self, doc_freq: usize) -> f32 {
+        let n = self.units.len() as f32;
+        let df = doc_freq as f32;
+        (1.0 + (n - df + 0.5) / (df + 0.5)).ln()
+    }
+
+    fn avg_title_len(&self) -> f32 {
+        self.total_title_len as f32 / self.units.len().max(1) as f32
+    }
+
+    fn avg_content_len(&self) -> f32 {
+        self.total_content_len as f32 / self.units.len().max(1) as f32
+    }
+}
+
+fn bm25_tf(tf: u32, len: usize, avg_len: f32) -> f32 {
+    if tf == 0 {
+        return 0.0;
+    }
+    let tf = tf as f32;
+    let norm = 1.0 - B + B * len as f32 / avg_len.max(1.0);
+    tf * (K1 + 1.0) / (tf + K1 * norm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(query: &str) -> Vec<String> {
+        tokenize(query)
+    }
+
+    fn ranked(index: &SearchIndex, query: &str) -> Vec<String> {
+        let mut scores: Vec<_> = index.score(&terms(query)).into_iter().collect();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scores.into_iter().map(|(id, _)| id).collect()
+    }
+
+    #[test]
+    fn tokenizes_into_lowercase_words_with_offsets() {
+        assert_eq!(tokenize("Hello, World! v2_beta"), ["hello", "world", "v2", "beta"]);
+        let text = "Grüße aus Köln";
+        let spans = token_spans(text);
+        assert_eq!(spans[2].1, "köln");
+        assert_eq!(&text[spans[2].0.clone()], "Köln");
+    }
+
+    #[test]
+    fn ranks_more_frequent_terms_higher() {
+        let mut index = SearchIndex::new();
+        index.insert("once", "Guide", "the worker starts and then stops");
+        index.insert("thrice", "Guide", "worker worker worker starts and then stops");
+        index.insert("none", "Guide", "nothing relevant in here at all");
+        assert_eq!(ranked(&index, "worker"), ["thrice", "once"]);
+    }
+
+    #[test]
+    fn ranks_rare_terms_higher_than_common_ones() {
+        let mut index = SearchIndex::new();
+        index.insert("a", "", "common rare");
+        index.insert("b", "", "common");
+        index.insert("c", "", "common");
+        let scores = index.score(&terms("common rare"));
+        assert!(scores["a"] > scores["b"]);
+        assert_eq!(scores["b"], scores["c"]);
+        assert!(index.score(&terms("rare"))["a"] > index.score(&terms("common"))["a"]);
+    }
+
+    #[test]
+    fn boosts_title_matches() {
+        let mut index = SearchIndex::new();
+        index.insert("body", "Overview", "routing is configured here");
+        index.insert("title", "Routing", "this page is configured here");
+        assert_eq!(ranked(&index, "routing"), ["title", "body"]);
+    }
+
+    #[test]
+    fn normalizes_by_document_length() {
+        let mut index = SearchIndex::new();
+        index.insert("short", "", "planner notes");
+        index.insert(
+            "long",
+            "",
+            "planner notes with a lot of unrelated words padding out this long document",
+        );
+        assert_eq!(ranked(&index, "planner"), ["short", "long"]);
+    }
+
+    #[test]
+    fn tracks_title_and_document_membership() {
+        let mut index = SearchIndex::new();
+        index.insert("doc", "Install Guide", "run the installer");
+        assert!(index.in_title("doc", "install"));
+        assert!(!index.in_title("doc", "installer"));
+        assert!(index.in_document("doc", "installer"));
+        assert!(!index.in_document("doc", "uninstall"));
+        assert_eq!(index.units_with("guide").collect::<Vec<_>>(), ["doc"]);
+    }
+
+    #[test]
+    fn removes_and_replaces_documents() {
+        let mut index = SearchIndex::new();
+        index.insert("a", "", "alpha beta");
+        index.insert("b", "", "beta");
+        index.remove("a");
+        assert!(index.score(&terms("alpha")).is_empty());
+        assert_eq!(index.units_with("beta").collect::<Vec<_>>(), ["b"]);
+
+        index.insert("b", "", "gamma");
+        assert!(index.score(&terms("beta")).is_empty());
+        assert!(index.in_document("b", "gamma"));
+        assert_eq!(index.total_content_len, 1);
+    }
+
+    #[test]
+    fn export_and_import_preserve_scores() {
+        let mut index = SearchIndex::new();
+        index.insert("a", "Deploy", "deploy the deploy service");
+        index.insert("b", "Operate", "operate the service");
+
+        let mut restored = SearchIndex::new();
+        for id in ["a", "b"] {
+            restored.import(index.export(id).unwrap());
+        }
+        assert_eq!(
+            restored.score(&terms("deploy service")),
+            index.score(&terms("deploy service"))
+        );
+        assert!(index.export("missing").is_none());
+    }
+}
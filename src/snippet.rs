@@ -0,0 +1,213 @@
+use serde::Serialize;
+use std::collections::HashSet;
+use std::ops::Range;
+
+use crate::search::token_spans;
+
+/// Controls how snippets are cut around matches
+#[derive(Debug, Clone, Copy)]
+pub struct SnippetOptions {
+    /// Lines of context to include before and after each matching line
+    pub context_lines: usize,
+    /// Maximum number of snippets to return per result
+    pub max_snippets: usize,
+}
+
+impl Default for SnippetOptions {
+    fn default() -> Self {
+        Self {
+            context_lines: 1,
+            max_snippets: 3,
+        }
+    }
+}
+
+/// A window of lines around one or more query matches
+#[derive(Debug, Clone, Serialize)]
+pub struct Snippet {
+    /// First line of the snippet in the document (1-based)
+    pub start_line: usize,
+    /// Last line of the snippet in the document (1-based, inclusive)
+    pub end_line: usize,
+    /// Snippet text as in the document; `matches` locates the matched terms in it
+    pub text: String,
+    pub matches: Vec<TermMatch>,
+}
+
+/// Location of a matched query term
+#[derive(Debug, Clone, Serialize)]
+pub struct TermMatch {
+    pub term: String,
+    /// Line in the document (1-based)
+    pub line: usize,
+    /// Byte offset within the line
+    pub column: usize,
+    /// Byte offset within the document
+    pub offset: usize,
+    /// Length of the match in bytes
+    pub length: usize,
+}
+
+struct Line<'a> {
+    text: &'a str,
+    offset: usize,
+    matches: Vec<(Range<usize>, String)>,
+}
+
+/// Build snippets for the query terms found in `text`.
+///
+/// `text` is a slice of a document starting at line `first_line` and byte
+/// `base_offset`, so reported positions are relative to the whole document.
+/// When no term matches, the opening lines are returned as a single snippet.
+pub fn build_snippets(
+    text: &str,
+    first_line: usize,
+    base_offset: usize,
+    terms: &[String],
+    options: SnippetOptions,
+) -> Vec<Snippet> {
+    let mut offset = base_offset;
+    let lines: Vec<Line> = text
+        .split_inclusive('\n')
+        .map(|raw| {
+            let line_offset = offset;
+            offset += raw.len();
+            let text = raw.trim_end_matches(['\n', '\r']);
+            let matches = token_spans(text)
+                .into_iter()
+                .filter(|(_, token)| terms.contains(token))
+                .collect();
+            Line {
+                text,
+                offset: line_offset,
+                matches,
+            }
+        })
+        .collect();
+    if lines.is_empty() || options.max_snippets == 0 {
+        return Vec::new();
+    }
+
+    // Merge overlapping context windows around matching lines
+    let ctx = options.context_lines;
+    let mut windows: Vec<Range<usize>> = Vec::new();
+    for (i, _) in lines.iter().enumerate().filter(|(_, l)| !l.matches.is_empty()) {
+        let start = i.saturating_sub(ctx);
+        let end = (i + ctx + 1).min(lines.len());
+        match windows.last_mut() {
+            Some(last) if start <= last.end => last.end = end,
+            _ => windows.push(start..end),
+        }
+    }
+    if windows.is_empty() {
+        windows.push(0..(2 * ctx + 1).min(lines.len()));
+    }
+
+    // Prefer windows covering the most distinct terms, then keep document order
+    let distinct_terms = |window: &Range<usize>| {
+        lines[window.clone()]
+            .iter()
+            .flat_map(|l| l.matches.iter().map(|(_, t)| t.as_str()))
+            .collect::<HashSet<_>>()
+            .len()
+    };
+    windows.sort_by(|a, b| {
+        distinct_terms(b)
+            .cmp(&distinct_terms(a))
+            .then_with(|| a.start.cmp(&b.start))
+    });
+    windows.truncate(options.max_snippets);
+    windows.sort_by_key(|w| w.start);
+
+    windows
+        .into_iter()
+        .map(|window| {
+            let mut text_lines = Vec::new();
+            let mut matches = Vec::new();
+            for (i, line) in lines.iter().enumerate().take(window.end).skip(window.start) {
+                let line_no = first_line + i;
+                for (span, term) in &line.matches {
+                    matches.push(TermMatch {
+                        term: term.clone(),
+                        line: line_no,
+                        column: span.start,
+                        offset: line.offset + span.start,
+                        length: span.len(),
+                    });
+                }
+                text_lines.push(line.text);
+            }
+            Snippet {
+                start_line: first_line + window.start,
+                end_line: first_line + window.end - 1,
+                text: text_lines.join("\n"),
+                matches,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(terms: &[&str]) -> Vec<String> {
+        terms.iter().map(|t| t.to_string()).collect()
+    }
+
+    fn options(context_lines: usize, max_snippets: usize) -> SnippetOptions {
+        SnippetOptions {
+            context_lines,
+            max_snippets,
+        }
+    }
+
+    #[test]
+    fn cuts_context_windows_around_matches() {
+        let text = "one\ntwo\nthree worker\nfour\nfive\nsix\nseven\nworker eight\nnine\n";
+        let snippets = build_snippets(text, 1, 0, &terms(&["worker"]), options(1, 3));
+        let lines: Vec<_> = snippets.iter().map(|s| (s.start_line, s.end_line)).collect();
+        assert_eq!(lines, [(2, 4), (7, 9)]);
+        assert_eq!(snippets[0].text, "two\nthree worker\nfour");
+    }
+
+    #[test]
+    fn merges_overlapping_windows() {
+        let text = "a\nworker\nb\nworker\nc\n";
+        let snippets = build_snippets(text, 1, 0, &terms(&["worker"]), options(1, 3));
+        assert_eq!(snippets.len(), 1);
+        assert_eq!((snippets[0].start_line, snippets[0].end_line), (1, 5));
+        assert_eq!(snippets[0].matches.len(), 2);
+    }
+
+    #[test]
+    fn prefers_windows_with_more_distinct_terms() {
+        let text = "kv\n\n\n\nkv\n\n\n\nkv router\n";
+        let snippets = build_snippets(text, 1, 0, &terms(&["kv", "router"]), options(0, 2));
+        let lines: Vec<_> = snippets.iter().map(|s| s.start_line).collect();
+        assert_eq!(lines, [1, 9]);
+    }
+
+    #[test]
+    fn reports_positions_relative_to_the_document() {
+        let document = "# Title\nIntro\n## Usage\nStart the Router now\n";
+        let start = document.find("## Usage").unwrap();
+        let snippets = build_snippets(&document[start..], 3, start, &terms(&["router"]), options(0, 3));
+        let found = &snippets[0].matches[0];
+        assert_eq!((found.line, found.column, found.length), (4, 10, 6));
+        assert_eq!(&document[found.offset..found.offset + found.length], "Router");
+        // The text is left as in the document, without highlighting
+        assert_eq!(snippets[0].text, "Start the Router now");
+    }
+
+    #[test]
+    fn falls_back_to_the_opening_lines() {
+        let text = "first\nsecond\nthird\nfourth\n";
+        let snippets = build_snippets(text, 5, 0, &terms(&["missing"]), options(1, 3));
+        assert_eq!(snippets.len(), 1);
+        assert_eq!((snippets[0].start_line, snippets[0].end_line), (5, 7));
+        assert!(snippets[0].matches.is_empty());
+        assert!(build_snippets("", 1, 0, &terms(&["a"]), options(1, 3)).is_empty());
+        assert!(build_snippets(text, 1, 0, &terms(&["first"]), options(1, 0)).is_empty());
+    }
+}
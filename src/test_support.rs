@@ -0,0 +1,150 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Directory under the system temp directory that is removed when dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Empty directory named after the test run and `name`, which must be unique among
+    /// the tests
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!(
+            "dynamo-mcp-test-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Path and `If-None-Match` header of each request a test server received
+pub type Requests = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+/// Status, extra headers and body of a test server response
+pub type Reply = (u16, Vec<(&'static str, String)>, String);
+
+/// Serve HTTP on a local port, answering each request with
+/// `respond(base_url, path, if_none_match, request_number)`. Returns the base URL and
+/// the requests received so far.
+pub fn serve(respond: impl Fn(&str, &str, Option<&str>, usize) -> Reply + Send + 'static) -> (String, Requests) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Requests::default();
+    let seen = requests.clone();
+    let url = base.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let path = line.split(' ').nth(1).unwrap_or_default().to_string();
+            let mut etag = None;
+            loop {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("if-none-match") {
+                        etag = Some(value.trim().to_string());
+                    }
+                }
+            }
+
+            let number = {
+                let mut seen = seen.lock().unwrap();
+                seen.push((path.clone(), etag.clone()));
+                seen.len()
+            };
+            let (status, headers, body) = respond(&base, &path, etag.as_deref(), number);
+            let reason = reqwest::StatusCode::from_u16(status)
+                .ok()
+                .and_then(|s| s.canonical_reason())
+                .unwrap_or_default();
+            let mut response = format!("HTTP/1.1 {} {}\r\n", status, reason);
+            for (name, value) in headers {
+                response.push_str(&format!("{}: {}\r\n", name, value));
+            }
+            response.push_str(&format!(
+                "Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            ));
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    (url, requests)
+}
+
+/// `If-None-Match` header of each request a test server received
+pub fn etags(requests: &Requests) -> Vec<Option<String>> {
+    requests.lock().unwrap().iter().map(|(_, etag)| etag.clone()).collect()
+}
+
+/// Path of each request a test server received
+pub fn paths(requests: &Requests) -> Vec<String> {
+    requests.lock().unwrap().iter().map(|(path, _)| path.clone()).collect()
+}
+
+/// Run git in `dir` with a fixed identity, committing and tagging at `date`. Returns
+/// its trimmed output and panics if it fails.
+pub fn git_at(dir: &Path, date: &str, args: &[&str]) -> String {
+    let output = std::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_AUTHOR_NAME", "Test")
+        .env("GIT_AUTHOR_EMAIL", "test@example.com")
+        .env("GIT_AUTHOR_DATE", date)
+        .env("GIT_COMMITTER_NAME", "Test")
+        .env("GIT_COMMITTER_EMAIL", "test@example.com")
+        .env("GIT_COMMITTER_DATE", date)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// Run git in `dir` with a fixed identity and date, see `git_at`
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    git_at(dir, "2025-01-01T00:00:00Z", args)
+}
+
+/// Repository `o/r` at `<dir>/remote/o/r.git` with `README.md` and `docs/guide.md`
+/// committed on `main` and tagged `v1.0.0`, and the config that clones it over `file://`
+pub fn remote_repo(dir: &Path) -> (PathBuf, crate::forge::ForgeConfig) {
+    let remote = dir.join("remote").join("o").join("r.git");
+    std::fs::create_dir_all(remote.join("docs")).unwrap();
+    std::fs::write(remote.join("README.md"), "# R\nAbout r\n").unwrap();
+    std::fs::write(remote.join("docs").join("guide.md"), "# Guide\nInstall it\n").unwrap();
+    git(&remote, &["init", "-q", "-b", "main"]);
+    git(&remote, &["add", "."]);
+    git(&remote, &["commit", "-q", "-m", "Initial"]);
+    git(&remote, &["tag", "-a", "v1.0.0", "-m", "First release"]);
+    let config = crate::forge::ForgeConfig {
+        kind: crate::forge::ForgeKind::Git,
+        api_url: None,
+        clone_url: Some(format!("file://{}/remote/{{owner}}/{{repo}}.git", dir.display())),
+    };
+    (remote, config)
+}
@@ -0,0 +1,46 @@
+use anyhow::Result;
+use jsonrpc_core::IoHandler;
+use serde_json::json;
+use std::io::{self, Write};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::task::JoinSet;
+
+/// Serve line-delimited JSON-RPC over stdin/stdout until stdin is closed.
+///
+/// Each request is handled in its own task so a slow tool call does not hold up
+/// other requests. Pending requests are completed before returning.
+pub async fn serve_stdio(io: IoHandler) -> Result<()> {
+    let io = Arc::new(io);
+    let mut pending = JoinSet::new();
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let io = io.clone();
+        pending.spawn(async move {
+            if let Some(response) = io.handle_request(&line).await {
+                write_message(&response);
+            }
+        });
+        while pending.try_join_next().is_some() {}
+    }
+
+    while pending.join_next().await.is_some() {}
+    Ok(())
+}
+
+/// Send a JSON-RPC notification (a message without an id) to the client
+pub fn notify(method: &str) {
+    write_message(&json!({ "jsonrpc": "2.0", "method": method }).to_string());
+}
+
+/// Write one message per line. Holding the stdout lock for the whole write keeps
+/// responses and notifications from different tasks and threads from interleaving.
+fn write_message(message: &str) {
+    let mut out = io::stdout().lock();
+    let _ = writeln!(out, "{}", message);
+    let _ = out.flush();
+}
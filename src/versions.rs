@@ -0,0 +1,219 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use semver::{Version, VersionReq};
+use std::cmp::Ordering;
+
+/// A version requested by a client, before it is resolved to a git ref.
+///
+/// - `latest` / `latest-prerelease`: the highest released version, optionally
+///   including prereleases
+/// - semver requirements such as `~0.3`, `^1.2` or `>=0.4,<0.5`
+/// - `<branch>@<YYYY-MM-DD>`: the branch as it was at the end of that day (UTC)
+/// - anything else is used as a branch, tag or commit as-is
+#[derive(Debug, Clone, PartialEq)]
+pub enum VersionSpec {
+    Latest,
+    LatestPrerelease,
+    Requirement(VersionReq),
+    AtDate { branch: String, date: NaiveDate },
+    Ref(String),
+}
+
+impl VersionSpec {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        match spec {
+            "latest" => return Ok(Self::Latest),
+            "latest-prerelease" => return Ok(Self::LatestPrerelease),
+            _ => {}
+        }
+
+        if let Some((branch, date)) = spec.rsplit_once('@') {
+            if let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+                if branch.is_empty() {
+                    anyhow::bail!("Missing branch in version {:?}", spec);
+                }
+                return Ok(Self::AtDate {
+                    branch: branch.to_string(),
+                    date,
+                });
+            }
+        }
+
+        // Only strings starting with an operator are ranges, so tags like `0.3` or
+        // `v1.0.0` keep naming that exact ref
+        if spec.starts_with(['~', '^', '>', '<', '=', '*']) {
+            let requirement = VersionReq::parse(&strip_v_prefixes(spec))
+                .with_context(|| format!("Invalid version requirement {:?}", spec))?;
+            return Ok(Self::Requirement(requirement));
+        }
+
+        Ok(Self::Ref(spec.to_string()))
+    }
+
+    /// Whether resolving the spec needs the list of released versions
+    pub fn needs_releases(&self) -> bool {
+        matches!(
+            self,
+            Self::Latest | Self::LatestPrerelease | Self::Requirement(_)
+        )
+    }
+
+    /// Pick the tag with the highest version satisfying the spec. Tags that are not
+    /// semantic versions (with or without a `v` prefix) are ignored.
+    pub fn select<'a>(&self, tags: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+        tags.into_iter()
+            .filter_map(|tag| parse_tag(tag).map(|version| (version, tag)))
+            .filter(|(version, _)| match self {
+                Self::Latest => version.pre.is_empty(),
+                Self::LatestPrerelease => true,
+                Self::Requirement(requirement) => requirement.matches(version),
+                Self::AtDate { .. } | Self::Ref(_) => false,
+            })
+            .max_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, tag)| tag)
+    }
+}
+
+/// Parse a tag such as `v0.4.1`, `0.4` or `v1.0.0-rc.1` as a semantic version
+pub fn parse_tag(tag: &str) -> Option<Version> {
+    let version = tag.strip_prefix(['v', 'V']).unwrap_or(tag);
+    if let Ok(version) = Version::parse(version) {
+        return Some(version);
+    }
+
+    // Pad short versions like `0.4` to `0.4.0`, keeping any prerelease suffix
+    let (core, suffix) = match version.find(['-', '+']) {
+        Some(i) => version.split_at(i),
+        None => (version, ""),
+    };
+    let parts = core.split('.').count();
+    if !(1..3).contains(&parts) || !core.split('.').all(|p| p.parse::<u64>().is_ok()) {
+        return None;
+    }
+    Version::parse(&format!("{}{}{}", core, ".0".repeat(3 - parts), suffix)).ok()
+}
+
+/// Order tags by descending semantic version, followed by tags that aren't versions
+/// in name order
+pub fn compare_tags(a: &str, b: &str) -> Ordering {
+    match (parse_tag(a), parse_tag(b)) {
+        (Some(va), Some(vb)) => vb.cmp(&va).then_with(|| a.cmp(b)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.cmp(b),
+    }
+}
+
+/// `semver` requirements don't accept a `v` prefix, but tags commonly carry one
+fn strip_v_prefixes(spec: &str) -> String {
+    spec.split(',')
+        .map(|part| {
+            let part = part.trim();
+            let operator_len = part.len() - part.trim_start_matches(['~', '^', '>', '<', '=']).len();
+            let (operator, version) = part.split_at(operator_len);
+            format!("{}{}", operator, version.trim_start().trim_start_matches(['v', 'V']))
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TAGS: &[&str] = &[
+        "v0.3.0",
+        "v0.3.2",
+        "v0.4.0-rc.1",
+        "0.4",
+        "v0.4.1",
+        "v1.0.0-beta.2",
+        "nightly",
+        "release-2024",
+    ];
+
+    fn select(spec: &str) -> Option<&'static str> {
+        VersionSpec::parse(spec).unwrap().select(TAGS.iter().copied())
+    }
+
+    #[test]
+    fn parses_aliases_ranges_dates_and_refs() {
+        assert_eq!(VersionSpec::parse(" latest ").unwrap(), VersionSpec::Latest);
+        assert_eq!(
+            VersionSpec::parse("latest-prerelease").unwrap(),
+            VersionSpec::LatestPrerelease
+        );
+        assert_eq!(
+            VersionSpec::parse(">=v0.4, <v0.5").unwrap(),
+            VersionSpec::Requirement(VersionReq::parse(">=0.4, <0.5").unwrap())
+        );
+        assert_eq!(
+            VersionSpec::parse("release/1.x@2024-03-01").unwrap(),
+            VersionSpec::AtDate {
+                branch: "release/1.x".to_string(),
+                date: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            }
+        );
+        // Tags that look like versions, and `@` without a date, name refs
+        for spec in ["0.3", "v1.0.0", "main", "user@example", "abc123"] {
+            assert_eq!(VersionSpec::parse(spec).unwrap(), VersionSpec::Ref(spec.to_string()));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_specs() {
+        assert!(VersionSpec::parse("~banana").is_err());
+        assert!(VersionSpec::parse("@2024-03-01").is_err());
+    }
+
+    #[test]
+    fn only_aliases_and_ranges_need_releases() {
+        assert!(VersionSpec::parse("latest").unwrap().needs_releases());
+        assert!(VersionSpec::parse("^0.4").unwrap().needs_releases());
+        assert!(!VersionSpec::parse("main").unwrap().needs_releases());
+        assert!(!VersionSpec::parse("main@2024-01-01").unwrap().needs_releases());
+    }
+
+    #[test]
+    fn resolves_aliases() {
+        assert_eq!(select("latest"), Some("v0.4.1"));
+        assert_eq!(select("latest-prerelease"), Some("v1.0.0-beta.2"));
+        assert_eq!(
+            VersionSpec::Latest.select(["v1.0.0-rc.1", "nightly"]),
+            None
+        );
+    }
+
+    #[test]
+    fn resolves_ranges() {
+        assert_eq!(select("~0.3"), Some("v0.3.2"));
+        assert_eq!(select("^0.4"), Some("v0.4.1"));
+        assert_eq!(select("=0.4.0"), Some("0.4"));
+        assert_eq!(select(">=v0.3.1,<0.4"), Some("v0.3.2"));
+        assert_eq!(select(">=0.4.0-rc.1, <0.4.0"), Some("v0.4.0-rc.1"));
+        assert_eq!(select("^2"), None);
+        assert_eq!(select("main"), None);
+    }
+
+    #[test]
+    fn parses_tags_as_versions() {
+        assert_eq!(parse_tag("v0.4.1"), Some(Version::new(0, 4, 1)));
+        assert_eq!(parse_tag("V2"), Some(Version::new(2, 0, 0)));
+        assert_eq!(parse_tag("0.4"), Some(Version::new(0, 4, 0)));
+        assert_eq!(parse_tag("v1.0-rc.1"), Version::parse("1.0.0-rc.1").ok());
+        assert_eq!(parse_tag("nightly"), None);
+        assert_eq!(parse_tag("v1.x"), None);
+        assert_eq!(parse_tag("1.2.3.4"), None);
+    }
+
+    #[test]
+    fn orders_tags_by_descending_version_then_name() {
+        let mut tags = vec!["nightly", "v0.3.2", "alpha", "v1.0.0-rc.1", "v1.0.0", "0.3.2"];
+        tags.sort_by(|a, b| compare_tags(a, b));
+        assert_eq!(
+            tags,
+            ["v1.0.0", "v1.0.0-rc.1", "0.3.2", "v0.3.2", "alpha", "nightly"]
+        );
+    }
+}
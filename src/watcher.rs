@@ -0,0 +1,137 @@
+use anyhow::Result;
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+use walkdir::WalkDir;
+
+use crate::repo_manager::RepoManager;
+use crate::rules::IndexRules;
+use crate::transport;
+use crate::{relative_path, DocumentIndex};
+
+/// How long to wait for more events before re-indexing a batch of changes
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watch local repository checkouts and re-index markdown files as they are
+/// created, modified or deleted, notifying the client when resources change.
+///
+/// The returned watcher must be kept alive for as long as changes should be tracked.
+pub fn watch_local_repos(
+    repos: Vec<(String, PathBuf)>,
+    index: Arc<RwLock<DocumentIndex>>,
+    repo_manager: Arc<RwLock<RepoManager>>,
+) -> Result<Option<RecommendedWatcher>> {
+    if repos.is_empty() {
+        return Ok(None);
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    for (name, path) in &repos {
+        watcher.watch(path, RecursiveMode::Recursive)?;
+        info!("Watching {} at {:?} for changes", name, path);
+    }
+
+    thread::spawn(move || {
+        // Which files of each checkout are indexed, to drop other changes early
+        let watched: Vec<(PathBuf, IndexRules)> = {
+            let index = index.blocking_read();
+            repos
+                .iter()
+                .filter_map(|(name, path)| Some((path.clone(), index.rules.get(name)?.clone())))
+                .collect()
+        };
+        let relevant = |path: &Path| {
+            watched
+                .iter()
+                .any(|(base_path, rules)| path.starts_with(base_path) && !ignored(base_path, rules, path))
+        };
+
+        while let Ok(event) = rx.recv() {
+            let mut paths = BTreeSet::new();
+            collect_paths(event, &relevant, &mut paths);
+            while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+                collect_paths(event, &relevant, &mut paths);
+            }
+
+            let mut changed = 0;
+            for (name, base_path) in &repos {
+                // Skip repositories that have since been switched away from the checkout
+                if repo_manager.blocking_read().get_path(name).as_deref() != Some(base_path.as_path()) {
+                    continue;
+                }
+                let mut index = index.blocking_write();
+                for path in paths.iter().filter(|p| p.starts_with(base_path)) {
+                    match index.update_path(name, base_path, path) {
+                        Ok(true) => changed += 1,
+                        Ok(false) => {}
+                        Err(e) => warn!("Failed to re-index {:?}: {}", path, e),
+                    }
+                }
+            }
+
+            if changed > 0 {
+                info!("Re-indexed {} changed files", changed);
+                transport::notify("notifications/resources/list_changed");
+            }
+        }
+    });
+
+    Ok(Some(watcher))
+}
+
+/// Whether a change in a checkout can't affect its index: it is under a hidden or
+/// excluded directory, or is a markdown file the rules don't select
+fn ignored(base_path: &Path, rules: &IndexRules, path: &Path) -> bool {
+    let relative = relative_path(base_path, path);
+    let components: Vec<&str> = relative.split('/').collect();
+    if components.iter().any(|c| c.starts_with('.')) {
+        return true;
+    }
+    if (1..=components.len()).any(|n| rules.excludes_dir(&components[..n].join("/"))) {
+        return true;
+    }
+    relative.ends_with(".md") && !rules.matches(&relative)
+}
+
+fn collect_paths(
+    event: notify::Result<Event>,
+    relevant: &dyn Fn(&Path) -> bool,
+    paths: &mut BTreeSet<PathBuf>,
+) {
+    let event = match event {
+        Ok(event) => event,
+        Err(e) => {
+            warn!("File watcher error: {}", e);
+            return;
+        }
+    };
+    if matches!(event.kind, EventKind::Access(_)) {
+        return;
+    }
+    for path in event.paths.into_iter().filter(|p| relevant(p)) {
+        // A directory created or moved into place: pick up the files inside it
+        let added = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_)));
+        if added && path.is_dir() {
+            paths.extend(files_under(&path, relevant));
+        } else {
+            paths.insert(path);
+        }
+    }
+}
+
+fn files_under<'a>(dir: &Path, relevant: &'a dyn Fn(&Path) -> bool) -> impl Iterator<Item = PathBuf> + 'a {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(move |e| relevant(e.path()))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+}